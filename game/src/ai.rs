@@ -0,0 +1,192 @@
+use crate::ball::{Ball, BallId};
+use crate::controller::PaddleController;
+use crate::paddle::{Paddle, PaddleRole};
+use crate::rng::Rng;
+use glam::Vec2;
+use std::collections::{HashMap, VecDeque};
+
+/// Computer player: moves each paddle to where the next ball will reach its ring.
+///
+/// Like a human, it reacts with a delay and misses its target by a random error.
+#[derive(Debug, Clone)]
+pub struct AiController {
+    /// number of steps between what the AI sees and what it does
+    pub reaction_ticks: usize,
+    /// max error (radian) on the predicted angle, a new error is picked for every ball trajectory
+    pub max_error: f32,
+    rng: Rng,
+    decisions: HashMap<PaddleRole, VecDeque<Option<f32>>>,
+    /// current error by paddle, and the trajectory (ball, impacts) it's used for
+    errors: HashMap<PaddleRole, ((BallId, i32), f32)>,
+}
+
+impl Default for AiController {
+    /// An AI a bit slower than a human (~0.1s of reaction at 120 steps per second).
+    fn default() -> Self {
+        AiController::new(12, 0.05, 0)
+    }
+}
+
+impl AiController {
+    pub fn new(reaction_ticks: usize, max_error: f32, seed: u64) -> Self {
+        AiController {
+            reaction_ticks,
+            max_error,
+            rng: Rng::new(seed),
+            decisions: HashMap::new(),
+            errors: HashMap::new(),
+        }
+    }
+
+    /// Forget the pending decisions (eg at the start of a round).
+    pub fn reset(&mut self) {
+        self.decisions.clear();
+        self.errors.clear();
+    }
+
+    fn decide(&mut self, paddle: &Paddle, balls: &[Ball]) -> Option<f32> {
+        // the first ball to reach the ring, else the nearest one
+        let target = balls
+            .iter()
+            .filter_map(|ball| {
+                find_paddle_crossing(paddle, ball.position, ball.mvt_dir)
+                    .map(|(distance, point)| (ball, distance, point))
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(ball, _, point)| (ball, point))
+            .or_else(|| {
+                balls
+                    .iter()
+                    .min_by(|a, b| {
+                        let distance = |ball: &Ball| paddle.distance_to_ring(ball.position);
+                        distance(a)
+                            .partial_cmp(&distance(b))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .map(|ball| (ball, ball.position))
+            });
+        let (ball, point) = target?;
+        let trajectory = (ball.id, ball.velocity_indicator);
+        let max_error = self.max_error;
+        let rng = &mut self.rng;
+        let error = match self.errors.get(&paddle.role) {
+            Some((known, error)) if *known == trajectory => *error,
+            _ => {
+                let error = rng.range(-max_error, max_error);
+                self.errors.insert(paddle.role, (trajectory, error));
+                error
+            }
+        };
+        Some(point.y.atan2(point.x) + error)
+    }
+}
+
+impl PaddleController for AiController {
+    fn target_angle(&mut self, paddle: &Paddle, balls: &[Ball], _tick: u64) -> Option<f32> {
+        let decision = self.decide(paddle, balls);
+        let reaction_ticks = self.reaction_ticks;
+        let pending = self.decisions.entry(paddle.role).or_default();
+        pending.push_back(decision);
+        if pending.len() > reaction_ticks {
+            pending.pop_front().flatten()
+        } else {
+            None
+        }
+    }
+}
+
+/// Where the straight trajectory from `position` along `direction` (normalized) reaches the ring
+/// of the paddle (its circle or its track): `(distance along the trajectory, point)`.
+fn find_paddle_crossing(paddle: &Paddle, position: Vec2, direction: Vec2) -> Option<(f32, Vec2)> {
+    match paddle.track.as_ref() {
+        Some(track) => track
+            .find_crossing(position, direction)
+            .map(|(distance, point, _)| (distance, point)),
+        None => find_ring_crossing(position, direction, paddle.radius_origin),
+    }
+}
+
+/// Where the straight trajectory from `position` along `direction` (normalized) reaches the
+/// circle of `radius` centered on the origin: `(distance along the trajectory, point)`.
+pub fn find_ring_crossing(position: Vec2, direction: Vec2, radius: f32) -> Option<(f32, Vec2)> {
+    let b = position.dot(direction);
+    let c = position.dot(position) - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt_discriminant = discriminant.sqrt();
+    // from inside the circle, the trajectory always crosses it (on the way out),
+    // from outside, only when going toward the circle (first crossing, on the way in)
+    let distance = if c < 0.0 {
+        -b + sqrt_discriminant
+    } else {
+        -b - sqrt_discriminant
+    };
+    if distance < 0.0 {
+        return None;
+    }
+    Some((distance, position + direction * distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::controlled_inputs;
+    use crate::simulation::{Event, Simulation};
+
+    #[test]
+    fn test_find_ring_crossing() {
+        let (distance, point) =
+            find_ring_crossing(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), 100.0).unwrap();
+        assert_eq!(distance, 100.0);
+        assert_eq!(point, Vec2::new(100.0, 0.0));
+        // from outside, toward the center
+        let (distance, _) =
+            find_ring_crossing(Vec2::new(0.0, 200.0), Vec2::new(0.0, -1.0), 100.0).unwrap();
+        assert_eq!(distance, 100.0);
+        // from outside, going away or missing the circle
+        assert!(find_ring_crossing(Vec2::new(0.0, 200.0), Vec2::new(0.0, 1.0), 100.0).is_none());
+        assert!(find_ring_crossing(Vec2::new(150.0, 200.0), Vec2::new(0.0, -1.0), 100.0).is_none());
+    }
+
+    fn rally(ai: &mut AiController, seed: u64) -> usize {
+        let mut sim = Simulation::new();
+        sim.seed = seed;
+        sim.start();
+        while sim.tick < 120 * 60 {
+            let inputs = controlled_inputs(ai, &sim);
+            if sim
+                .step(1.0 / 120.0, &inputs)
+                .iter()
+                .any(|e| matches!(e, Event::GameOver))
+            {
+                break;
+            }
+        }
+        sim.scoreboard.score
+    }
+
+    #[test]
+    fn test_reaction_and_error_make_the_ai_weaker() {
+        let perfect: usize = (1..=5)
+            .map(|seed| rally(&mut AiController::new(0, 0.0, seed), seed))
+            .sum();
+        let slow: usize = (1..=5)
+            .map(|seed| rally(&mut AiController::new(60, 0.3, seed), seed))
+            .sum();
+        assert!(perfect > 20, "perfect: {}", perfect);
+        assert!(slow < perfect, "slow: {}, perfect: {}", slow, perfect);
+    }
+
+    #[test]
+    fn test_reaction_delay() {
+        let mut ai = AiController::new(2, 0.0, 0);
+        let mut sim = Simulation::new();
+        sim.start();
+        let paddle = sim.paddles[0].clone();
+        assert_eq!(ai.target_angle(&paddle, &sim.balls, 0), None);
+        assert_eq!(ai.target_angle(&paddle, &sim.balls, 1), None);
+        assert!(ai.target_angle(&paddle, &sim.balls, 2).is_some());
+    }
+}
@@ -0,0 +1,103 @@
+use crate::physics::positive_angle;
+use std::f32::consts::PI;
+
+/// Shortest signed rotation (in ]-PI, PI]) to go from angle `from` to angle `to`.
+pub fn angle_delta(from: f32, to: f32) -> f32 {
+    let delta = positive_angle(to - from);
+    if delta > PI {
+        delta - 2.0 * PI
+    } else {
+        delta
+    }
+}
+
+/// Shortest (unsigned) rotation between 2 angles, in [0, PI].
+pub fn angle_distance(a: f32, b: f32) -> f32 {
+    angle_delta(a, b).abs()
+}
+
+/// An interval of angles `[center - half_width, center + half_width]` on the circle,
+/// the seam at 0 / 2 PI is not a boundary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AngularInterval {
+    pub center: f32,
+    pub half_width: f32,
+}
+
+impl AngularInterval {
+    pub fn new(center: f32, half_width: f32) -> Self {
+        AngularInterval {
+            center: positive_angle(center),
+            half_width,
+        }
+    }
+
+    pub fn contains(&self, angle: f32) -> bool {
+        angle_distance(self.center, angle) <= self.half_width
+    }
+
+    /// Angular distance from `angle` to the nearest bound of the interval, 0 if `angle` is inside.
+    pub fn distance(&self, angle: f32) -> f32 {
+        (angle_distance(self.center, angle) - self.half_width).max(0.0)
+    }
+
+    /// Width of the common part of the 2 intervals, 0 if they are disjoint.
+    pub fn overlap(&self, other: &AngularInterval) -> f32 {
+        let gap = angle_distance(self.center, other.center);
+        (self.half_width + other.half_width - gap)
+            .max(0.0)
+            .min(2.0 * self.half_width.min(other.half_width))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    #[test]
+    fn test_angle_delta() {
+        assert_eq!(angle_delta(0.0, 0.5), 0.5);
+        assert_eq!(angle_delta(0.5, 0.0), -0.5);
+        assert!((angle_delta(6.2, 0.1) - (0.1 + 2.0 * PI - 6.2)).abs() < EPSILON);
+        assert!((angle_delta(0.1, 6.2) + (0.1 + 2.0 * PI - 6.2)).abs() < EPSILON);
+        assert!((angle_delta(-0.1, 0.1) - 0.2).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_contains_across_the_seam() {
+        let paddle = AngularInterval::new(6.2, PI / 12.0);
+        assert!(paddle.contains(0.1));
+        assert!(paddle.contains(2.0 * PI));
+        assert!(paddle.contains(-0.1));
+        assert!(!paddle.contains(0.3));
+        assert!(!paddle.contains(5.8));
+
+        let paddle = AngularInterval::new(0.0, PI / 12.0);
+        assert!(paddle.contains(6.2));
+        assert!(paddle.contains(0.2));
+        assert!(!paddle.contains(PI));
+    }
+
+    #[test]
+    fn test_distance_across_the_seam() {
+        let paddle = AngularInterval::new(6.2, 0.1);
+        assert_eq!(paddle.distance(6.25), 0.0);
+        let expected = 2.0 * PI - 6.2 + 0.2 - 0.1;
+        assert!((paddle.distance(0.2) - expected).abs() < EPSILON);
+        assert!((paddle.distance(-2.0 * PI + 0.2) - expected).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_overlap_across_the_seam() {
+        let a = AngularInterval::new(6.2, 0.2);
+        let b = AngularInterval::new(0.1, 0.2);
+        let expected = 0.4 - (2.0 * PI - 6.2 + 0.1);
+        assert!((a.overlap(&b) - expected).abs() < EPSILON);
+        assert!((b.overlap(&a) - expected).abs() < EPSILON);
+        assert_eq!(a.overlap(&AngularInterval::new(PI, 0.2)), 0.0);
+        // fully included
+        assert!((a.overlap(&AngularInterval::new(6.2, 0.05)) - 0.1).abs() < EPSILON);
+    }
+}
@@ -0,0 +1,135 @@
+//! Motion of the whole arena: it turns around its center and its rings breathe in and out.
+//!
+//! The motion is part of the simulation (not of the rendering) so the rounds stay reproducible
+//! (replays), and the balls bounce on the rings and the obstacles as they move (see
+//! `reflect_on_moving`).
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// The radius of the rings goes from `1 - amplitude` to `1 + amplitude` times their radius at rest.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pulse {
+    /// fraction of the radius, between 0 and 1
+    pub amplitude: f32,
+    /// seconds
+    pub period: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArenaMotion {
+    /// the rings (and their tracks), the obstacles and the teleporters turn around the center
+    /// (radian per second, counterclockwise), the players still aim the paddles in the window
+    #[serde(default)]
+    pub angular_speed: f32,
+    /// the rings (and their paddles) breathe in and out, the obstacles and the teleporters don't
+    #[serde(default)]
+    pub pulse: Option<Pulse>,
+    /// seconds since the start of the round
+    #[serde(skip)]
+    pub elapsed: f32,
+}
+
+impl ArenaMotion {
+    /// The arena doesn't move (the historical arena).
+    pub fn is_static(&self) -> bool {
+        self.angular_speed == 0.0 && self.pulse.is_none()
+    }
+
+    pub fn advance(&mut self, delta_seconds: f32) {
+        self.elapsed += delta_seconds;
+    }
+
+    /// The angle of the arena since the start of the round.
+    pub fn rotation(&self) -> f32 {
+        self.angular_speed * self.elapsed
+    }
+
+    /// The radius of the rings relative to their radius at rest.
+    pub fn scale(&self) -> f32 {
+        match self.pulse {
+            Some(pulse) if pulse.period > 0.0 => {
+                1.0 + pulse.amplitude * (2.0 * PI * self.elapsed / pulse.period).sin()
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// The extremes of `scale` during the round.
+    pub fn scale_range(&self) -> (f32, f32) {
+        match self.pulse {
+            Some(pulse) => (1.0 - pulse.amplitude, 1.0 + pulse.amplitude),
+            None => (1.0, 1.0),
+        }
+    }
+
+    /// The velocity (per second) of the point of the arena at `point`, due to the rotation.
+    pub fn rotation_velocity_at(&self, point: Vec2) -> Vec2 {
+        Vec2::new(-point.y, point.x) * self.angular_speed
+    }
+
+    /// The velocity (per second) of the point of a ring at `point`: the rotation and the pulse.
+    pub fn ring_velocity_at(&self, point: Vec2) -> Vec2 {
+        let pulse_speed = match self.pulse {
+            Some(pulse) if pulse.period > 0.0 => {
+                let pulsation = 2.0 * PI / pulse.period;
+                pulse.amplitude * pulsation * (pulsation * self.elapsed).cos() / self.scale()
+            }
+            _ => 0.0,
+        };
+        self.rotation_velocity_at(point) + point * pulse_speed
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.angular_speed.is_finite() {
+            return Err("angular_speed should be a number".to_string());
+        }
+        if let Some(pulse) = self.pulse {
+            if !(pulse.amplitude >= 0.0 && pulse.amplitude < 1.0) {
+                return Err("the amplitude of the pulse should be between 0 and 1".to_string());
+            }
+            if !(pulse.period > 0.0 && pulse.period.is_finite()) {
+                return Err("the period of the pulse should be > 0".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pulse_and_rotation() {
+        let mut arena = ArenaMotion {
+            angular_speed: 0.5,
+            pulse: Some(Pulse {
+                amplitude: 0.1,
+                period: 4.0,
+            }),
+            elapsed: 0.0,
+        };
+        assert!(!arena.is_static());
+        assert_eq!(arena.scale(), 1.0);
+        arena.advance(1.0);
+        assert_eq!(arena.rotation(), 0.5);
+        assert!((arena.scale() - 1.1).abs() < 1e-6);
+        // at the widest, the rings only turn
+        let velocity = arena.ring_velocity_at(Vec2::new(100.0, 0.0));
+        assert!((velocity - Vec2::new(0.0, 50.0)).length() < 1e-3);
+        arena.advance(1.0);
+        // shrinking
+        let velocity = arena.ring_velocity_at(Vec2::new(100.0, 0.0));
+        assert!(velocity.x < -15.0);
+        assert!(ArenaMotion::default().is_static());
+        arena.pulse = Some(Pulse {
+            amplitude: 1.0,
+            period: 4.0,
+        });
+        assert!(arena.validate().is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use crate::paddle::PaddleRole;
+use crate::speed_curve::SpeedCurve;
+use glam::Vec2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BallId(pub u32);
+
+/// A ball caught by a magnetic paddle: it moves with the paddle until it's released.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attachment {
+    /// index of the paddle in the `Simulation`
+    pub paddle: usize,
+    /// distance between the center of the ball and the center of the ring (see `Paddle::attach_distance`)
+    pub distance: f32,
+    /// angle of the ball relative to the angle of the paddle
+    pub angle_offset: f32,
+    /// angle of the normal of the surface (at the impact) relative to the angle of the paddle
+    pub normal_offset: f32,
+    /// number of steps since the catch
+    pub held_ticks: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ball {
+    pub id: BallId,
+    pub position: Vec2,
+    /// position at the previous step of the simulation, used to interpolate the rendering
+    pub position_previous: Vec2,
+    pub mvt_dir: Vec2,
+    pub velocity_indicator: i32,
+    pub radius: f32,
+    /// factor of the velocity given by the speed curve (see `PowerUps`)
+    pub speed_factor: f32,
+    /// the paddle that sent the ball
+    pub last_hit: Option<PaddleRole>,
+    /// caught by a magnetic paddle
+    pub attached: Option<Attachment>,
+    /// steps before the ball can be teleported again (see `Teleporter`)
+    pub teleport_cooldown: u64,
+    /// the direction turns (radian per second, counterclockwise), given by the spin of a paddle
+    /// (see `Spin`)
+    pub curve: f32,
+}
+
+impl Ball {
+    /// A ball that has not been hit yet, at the first velocity of the speed curve.
+    pub fn new(id: BallId, position: Vec2, mvt_dir: Vec2) -> Self {
+        Ball {
+            id,
+            position,
+            position_previous: position,
+            mvt_dir,
+            velocity_indicator: 0,
+            radius: 5.0,
+            speed_factor: 1.0,
+            last_hit: None,
+            attached: None,
+            teleport_cooldown: 0,
+            curve: 0.0,
+        }
+    }
+
+    /// A ball (of id 0) that moved from `from` to `to` during the last step.
+    #[cfg(test)]
+    pub fn moving(from: Vec2, to: Vec2) -> Self {
+        let mut ball = Ball::new(BallId(0), to, (to - from).normalize());
+        ball.position_previous = from;
+        ball
+    }
+
+    pub fn velocity(&self, curve: &SpeedCurve) -> f32 {
+        curve.velocity(self.velocity_indicator) * self.speed_factor
+    }
+
+    /// Position between the previous (`alpha == 0`) and the current step (`alpha == 1`).
+    pub fn position_interpolated(&self, alpha: f32) -> Vec2 {
+        self.position_previous + (self.position - self.position_previous) * alpha
+    }
+}
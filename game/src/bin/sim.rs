@@ -0,0 +1,219 @@
+//! Run rallies without window (no bevy), print a json report per round (one per line).
+//!
+//! ```sh
+//! cargo run --no-default-features --bin sim -- --bot follow --runs 1000 --ticks 36000
+//! cargo run --no-default-features --bin sim -- --replay <file>
+//! ```
+//!
+//! With `--env`, it's an environment for agents, driven by json lines on stdin (see `Env`):
+//!
+//! ```sh
+//! echo '{"cmd":"reset","seed":1}' | cargo run --no-default-features --bin sim -- --env
+//! ```
+
+use ld47_keep_inside::{
+    controlled_inputs, default_obstacles, default_teleporters, run_headless, AiController, Bot,
+    Difficulty, Env, FixedTimestep, Level, PlayMode, Replay, ReplayPlayer, Simulation,
+};
+use std::io::{BufReader, Write};
+
+const USAGE: &str = "usage: sim --env | [--replay <file> | --bot <idle|follow|ai>] [--level <file>] [--power-ups] [--obstacles] [--multiball] [--reaction <ticks>] [--error <radian>] [--ticks <n>] [--runs <n>] [--seed <n>] [--difficulty <easy|normal|hard>] [--mode <solo|coop|versus>]";
+
+struct Args {
+    env: bool,
+    replay: Option<String>,
+    /// a level file (RON) instead of the default arena
+    level: Option<String>,
+    /// spawn power-up zones
+    power_ups: bool,
+    /// bumpers, deflectors and teleporters between the rings
+    obstacles: bool,
+    /// extra balls from the power-ups and the score milestones
+    multiball: bool,
+    bot: Bot,
+    /// reaction delay of the ai bot
+    reaction_ticks: Option<usize>,
+    /// max error of the ai bot
+    max_error: Option<f32>,
+    ticks: u64,
+    runs: u64,
+    seed: u64,
+    difficulty: Difficulty,
+    play_mode: PlayMode,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            env: false,
+            replay: None,
+            level: None,
+            power_ups: false,
+            obstacles: false,
+            multiball: false,
+            bot: Bot::Follow,
+            reaction_ticks: None,
+            max_error: None,
+            // 5 minutes of play
+            ticks: 120 * 60 * 5,
+            runs: 1,
+            seed: 1,
+            difficulty: Difficulty::default(),
+            play_mode: PlayMode::default(),
+        }
+    }
+}
+
+/// The arguments (without the name of the program), `None` for `--help`.
+fn parse_args(command_line: impl IntoIterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut args = Args::default();
+    let mut it = command_line.into_iter();
+    while let Some(arg) = it.next() {
+        let mut value = || {
+            it.next()
+                .ok_or_else(|| format!("missing value for {}\n{}", arg, USAGE))
+        };
+        let invalid = |value: &str, err: &dyn std::fmt::Display| {
+            format!("invalid value '{}' for {}: {}", value, arg, err)
+        };
+        let number = |value: String| value.parse::<u64>().map_err(|err| invalid(&value, &err));
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(None),
+            "--env" => args.env = true,
+            "--power-ups" => args.power_ups = true,
+            "--obstacles" => args.obstacles = true,
+            "--multiball" => args.multiball = true,
+            "--replay" => args.replay = Some(value()?),
+            "--level" => args.level = Some(value()?),
+            "--bot" => args.bot = value()?.parse()?,
+            "--reaction" => args.reaction_ticks = Some(number(value()?)? as usize),
+            "--error" => {
+                let value = value()?;
+                args.max_error = Some(value.parse::<f32>().map_err(|err| invalid(&value, &err))?)
+            }
+            "--ticks" => args.ticks = number(value()?)?,
+            "--runs" => args.runs = number(value()?)?,
+            "--seed" => args.seed = number(value()?)?,
+            "--difficulty" => {
+                let value = value()?;
+                args.difficulty = match value.as_str() {
+                    "easy" => Difficulty::Easy,
+                    "normal" => Difficulty::Normal,
+                    "hard" => Difficulty::Hard,
+                    _ => return Err(format!("unknown difficulty '{}'\n{}", value, USAGE)),
+                }
+            }
+            "--mode" => {
+                let value = value()?;
+                args.play_mode = match value.as_str() {
+                    "solo" => PlayMode::Solo,
+                    "coop" => PlayMode::Coop,
+                    "versus" => PlayMode::Versus,
+                    _ => return Err(format!("unknown mode '{}'\n{}", value, USAGE)),
+                }
+            }
+            _ => return Err(format!("unknown argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+    Ok(Some(args))
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    if args.env {
+        let stdin = std::io::stdin();
+        Env::default().serve(stdin.lock(), out)?;
+    } else if let Some(path) = args.replay {
+        let replay = Replay::read(BufReader::new(std::fs::File::open(path)?))?;
+        let mut sim = replay.simulation();
+        let step_seconds = replay.config.step_seconds;
+        let ticks = args.ticks.min(replay.config.ticks);
+        let mut player = ReplayPlayer::new(replay);
+        let report = run_headless(&mut sim, step_seconds, ticks, |sim| {
+            controlled_inputs(&mut player, sim)
+        });
+        writeln!(out, "{}", serde_json::to_string(&report)?)?;
+    } else {
+        let step_seconds = FixedTimestep::default().step_seconds;
+        let level = match args.level.as_ref() {
+            Some(path) => Some(Level::from_ron(&std::fs::read_to_string(path)?)?),
+            None => None,
+        };
+        for run in 0..args.runs {
+            let mut sim = Simulation::new();
+            sim.speed_curve = args.difficulty.speed_curve();
+            sim.play_mode = args.play_mode;
+            sim.seed = args.seed + run;
+            sim.power_ups.enabled = args.power_ups;
+            if args.obstacles {
+                sim.obstacles = default_obstacles();
+                sim.teleporters = default_teleporters();
+            }
+            if let Some(level) = level.as_ref() {
+                level.apply(&mut sim, args.difficulty);
+            }
+            sim.multiball.enabled = args.multiball;
+            sim.start();
+            let mut bot = match &args.bot {
+                // a different error for every run
+                Bot::Ai(ai) => Bot::Ai(AiController::new(
+                    args.reaction_ticks.unwrap_or(ai.reaction_ticks),
+                    args.max_error.unwrap_or(ai.max_error),
+                    sim.seed,
+                )),
+                bot => bot.clone(),
+            };
+            let report = run_headless(&mut sim, step_seconds, args.ticks, |sim| {
+                controlled_inputs(&mut bot, sim)
+            });
+            writeln!(out, "{}", serde_json::to_string(&report)?)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(command_line: &str) -> Result<Option<Args>, String> {
+        parse_args(command_line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse("--bot ai --runs 10 --obstacles --difficulty hard")
+            .unwrap()
+            .unwrap();
+        assert_eq!(args.runs, 10);
+        assert!(args.obstacles);
+        assert_eq!(args.difficulty, Difficulty::Hard);
+        assert!(parse("--runs 1 --help").unwrap().is_none());
+        // the flag is checked before its value
+        let err = parse("--runs 1 --bogus").err().unwrap();
+        assert!(err.starts_with("unknown argument '--bogus'"), "{}", err);
+        let err = parse("--runs").err().unwrap();
+        assert!(err.starts_with("missing value for --runs"), "{}", err);
+        let err = parse("--runs ten").err().unwrap();
+        assert!(err.starts_with("invalid value 'ten' for --runs"), "{}", err);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::ai::AiController;
+use crate::ball::Ball;
+use crate::controller::PaddleController;
+use crate::paddle::Paddle;
+use std::fmt;
+use std::str::FromStr;
+
+/// Scripted players, to run rallies without a human (headless simulations, tests, ...).
+#[derive(Debug, Clone)]
+pub enum Bot {
+    /// the paddles don't move
+    Idle,
+    /// each paddle faces the ball the nearest of its ring
+    Follow,
+    /// each paddle goes where the next ball will reach its ring
+    Ai(AiController),
+}
+
+impl PaddleController for Bot {
+    fn target_angle(&mut self, paddle: &Paddle, balls: &[Ball], tick: u64) -> Option<f32> {
+        match self {
+            Bot::Idle => None,
+            Bot::Follow => {
+                let distance = |ball: &Ball| paddle.distance_to_ring(ball.position);
+                balls
+                    .iter()
+                    .min_by(|a, b| {
+                        distance(a)
+                            .partial_cmp(&distance(b))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .map(|ball| ball.position.y.atan2(ball.position.x))
+            }
+            Bot::Ai(ai) => ai.target_angle(paddle, balls, tick),
+        }
+    }
+}
+
+impl FromStr for Bot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "idle" => Ok(Bot::Idle),
+            "follow" => Ok(Bot::Follow),
+            "ai" => Ok(Bot::Ai(AiController::default())),
+            _ => Err(format!("unknown bot '{}' (expected idle, follow or ai)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Bot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bot::Idle => write!(f, "idle"),
+            Bot::Follow => write!(f, "follow"),
+            Bot::Ai(_) => write!(f, "ai"),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::simulation::Inputs;
+
+/// How the angles requested for each paddle are dispatched to the paddles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlMode {
+    /// one angle drives every paddle, the inner paddle is rotated by `inner_offset` (radian)
+    Linked { inner_offset: f32 },
+    /// each paddle follows its own source
+    Independent,
+}
+
+/// The default offset (radian) of the inner paddle in the second linked mode: opposite paddles.
+pub const DEFAULT_LINKED_OFFSET: f32 = std::f32::consts::PI;
+
+impl Default for ControlMode {
+    fn default() -> Self {
+        ControlMode::Linked { inner_offset: 0.0 }
+    }
+}
+
+impl ControlMode {
+    /// Build the `Inputs` of the simulation from the angles requested for the outer and the inner paddle.
+    ///
+    /// In linked mode, the outer angle drives both paddles. Without outer angle, the inner one (eg
+    /// from the keyboard) is the angle of the inner paddle: the outer paddle follows it, rotated
+    /// back by `inner_offset`.
+    pub fn dispatch(&self, outer_angle: Option<f32>, inner_angle: Option<f32>) -> Inputs {
+        match self {
+            ControlMode::Linked { inner_offset } => {
+                let angle = outer_angle.or_else(|| inner_angle.map(|angle| angle - inner_offset));
+                Inputs {
+                    outer_angle: angle,
+                    inner_angle: angle.map(|angle| angle + inner_offset),
+                    ..Inputs::default()
+                }
+            }
+            ControlMode::Independent => Inputs {
+                outer_angle,
+                inner_angle,
+                ..Inputs::default()
+            },
+        }
+    }
+
+    /// Cycle between the modes: linked, linked with the inner paddle rotated by `linked_offset`
+    /// (radian, eg `PI` for opposite paddles), independent.
+    pub fn next(&self, linked_offset: f32) -> ControlMode {
+        match self {
+            ControlMode::Linked { inner_offset }
+                if *inner_offset == 0.0 && linked_offset != 0.0 =>
+            {
+                ControlMode::Linked {
+                    inner_offset: linked_offset,
+                }
+            }
+            ControlMode::Linked { .. } => ControlMode::Independent,
+            ControlMode::Independent => ControlMode::default(),
+        }
+    }
+}
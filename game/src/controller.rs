@@ -0,0 +1,32 @@
+use crate::ball::Ball;
+use crate::paddle::Paddle;
+use crate::simulation::{Inputs, Simulation};
+
+/// Something that drives paddles: a device (mouse, gamepad, ...), a replay, an AI.
+pub trait PaddleController {
+    /// The angle requested for the `paddle` at the step that follows `tick`, `None` to keep the
+    /// current angle. It's called once per driving paddle (see `Simulation::driving_paddles`) and
+    /// per step.
+    fn target_angle(&mut self, paddle: &Paddle, balls: &[Ball], tick: u64) -> Option<f32>;
+
+    /// Release the balls caught by the (magnetic) `paddle` at the step that follows `tick`.
+    /// It's called once per driving paddle and per step, after `target_angle`.
+    fn release(&mut self, _paddle: &Paddle, _tick: u64) -> bool {
+        false
+    }
+}
+
+/// The `Inputs` of the next step of `sim`, when the `controller` drives every paddle (through the
+/// driving paddles, see `Simulation::driving_paddles`).
+pub fn controlled_inputs(controller: &mut dyn PaddleController, sim: &Simulation) -> Inputs {
+    let mut inputs = Inputs::default();
+    for paddle in sim.driving_paddles() {
+        if let Some(angle) = controller.target_angle(paddle, &sim.balls, sim.tick) {
+            inputs.set_angle(paddle.role, angle - paddle.offset);
+        }
+        if controller.release(paddle, sim.tick) {
+            inputs.set_release(paddle.role);
+        }
+    }
+    inputs
+}
@@ -0,0 +1,634 @@
+//! Edit a `Level` with a pointer (the mouse in the game, in the coordinates of the arena): pick and
+//! drag the rings, the obstacles, the pads of the teleporters and the spawns, add new ones with the
+//! current `Tool`.
+
+use crate::level::{Level, Ring, Spawn};
+use crate::obstacle::{Obstacle, Shape, DEFLECTOR_HALF_WIDTH};
+use crate::paddle::PaddleRole;
+use crate::physics::distance_to_segment;
+use crate::teleporter::{Exit, Pad, Teleporter};
+use glam::Vec2;
+use std::f32::consts::PI;
+use std::fmt;
+
+/// How far from an element the pointer can pick it.
+pub const PICK_DISTANCE: f32 = 8.0;
+/// Min room between the rings (and between the inner ring and the center).
+const MIN_RING_GAP: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tool {
+    /// pick and drag
+    #[default]
+    Select,
+    Bumper,
+    /// 2 clicks, one per pad
+    Teleporter,
+    Spawn,
+    /// a ring (and its paddle) between the rings, driven by the player of the nearest ring
+    Ring,
+}
+
+impl Tool {
+    pub fn next(&self) -> Tool {
+        match self {
+            Tool::Select => Tool::Bumper,
+            Tool::Bumper => Tool::Teleporter,
+            Tool::Teleporter => Tool::Spawn,
+            Tool::Spawn => Tool::Ring,
+            Tool::Ring => Tool::Select,
+        }
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Tool::Select => "select",
+            Tool::Bumper => "add a bumper",
+            Tool::Teleporter => "add a teleporter",
+            Tool::Spawn => "add a spawn",
+            Tool::Ring => "add a ring",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// An element of the level, by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Ring(usize),
+    Obstacle(usize),
+    Pad { teleporter: usize, pad: usize },
+    Spawn(usize),
+}
+
+pub struct LevelEditor {
+    pub level: Level,
+    pub tool: Tool,
+    pub selected: Option<Target>,
+    /// the first pad of the teleporter being added
+    pub pending_pad: Option<Vec2>,
+    /// the element moved by the pointer, and its offset from the pointer
+    dragging: Option<(Target, Vec2)>,
+    changed: bool,
+}
+
+impl LevelEditor {
+    pub fn new(level: Level) -> Self {
+        LevelEditor {
+            level,
+            tool: Tool::default(),
+            selected: None,
+            pending_pad: None,
+            dragging: None,
+            changed: false,
+        }
+    }
+
+    /// Did the level change since the previous call.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::replace(&mut self.changed, false)
+    }
+
+    /// The radius of the nearest rings inside and outside of the ring `index`.
+    fn neighbour_radii(&self, index: usize) -> (Option<f32>, Option<f32>) {
+        let radius = self.level.rings[index].radius;
+        let others = || {
+            self.level
+                .rings
+                .iter()
+                .enumerate()
+                .filter(move |(other, _)| *other != index)
+                .map(|(_, ring)| ring.radius)
+        };
+        (
+            others().filter(|other| *other < radius).reduce(f32::max),
+            others().filter(|other| *other > radius).reduce(f32::min),
+        )
+    }
+
+    /// The element under the `point`, the nearest if there are several.
+    pub fn pick(&self, point: Vec2) -> Option<Target> {
+        let near_center = |center: Vec2, radius: f32| ((point - center).length() - radius).max(0.0);
+        let mut candidates = vec![];
+        for (index, spawn) in self.level.spawns.iter().enumerate() {
+            candidates.push((Target::Spawn(index), near_center(spawn.position, 0.0)));
+        }
+        for (index, teleporter) in self.level.teleporters.iter().enumerate() {
+            for (pad_index, pad) in teleporter.pads.iter().enumerate() {
+                candidates.push((
+                    Target::Pad {
+                        teleporter: index,
+                        pad: pad_index,
+                    },
+                    near_center(pad.center, pad.radius),
+                ));
+            }
+        }
+        for (index, obstacle) in self.level.obstacles.iter().enumerate() {
+            let distance = match obstacle.shape {
+                Shape::Bumper { center, radius } => near_center(center, radius),
+                Shape::Deflector { start, end } => {
+                    (distance_to_segment(point, start, end) - DEFLECTOR_HALF_WIDTH).max(0.0)
+                }
+            };
+            candidates.push((Target::Obstacle(index), distance));
+        }
+        for (index, ring) in self.level.rings.iter().enumerate() {
+            candidates.push((
+                Target::Ring(index),
+                (point.length() - ring.radius_at(point)).abs(),
+            ));
+        }
+        candidates
+            .into_iter()
+            .filter(|(_, distance)| *distance <= PICK_DISTANCE)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(target, _)| target)
+    }
+
+    /// Click with the current tool.
+    pub fn press(&mut self, point: Vec2) {
+        if self.tool != Tool::Select {
+            // outside of the arena (eg on a button)
+            let outer = self
+                .level
+                .rings
+                .iter()
+                .map(|ring| ring.radius)
+                .reduce(f32::max)
+                .unwrap_or(f32::MAX);
+            if point.length() >= outer {
+                return;
+            }
+        }
+        match self.tool {
+            Tool::Select => {
+                self.selected = self.pick(point);
+                self.dragging = self
+                    .selected
+                    .map(|target| (target, self.position_of(target).unwrap_or(point) - point));
+            }
+            Tool::Bumper => {
+                self.level.obstacles.push(Obstacle {
+                    points: 1,
+                    ..Obstacle::bumper(point, 10.0)
+                });
+                self.select_added(Target::Obstacle(self.level.obstacles.len() - 1));
+            }
+            Tool::Teleporter => match self.pending_pad.take() {
+                None => self.pending_pad = Some(point),
+                Some(first) => {
+                    // oriented toward the outside
+                    let pad = |center: Vec2| Pad {
+                        center,
+                        radius: 14.0,
+                        angle: center.y.atan2(center.x),
+                    };
+                    self.level.teleporters.push(Teleporter::new(
+                        pad(first),
+                        pad(point),
+                        Exit::Rotate,
+                    ));
+                    self.select_added(Target::Pad {
+                        teleporter: self.level.teleporters.len() - 1,
+                        pad: 1,
+                    });
+                }
+            },
+            Tool::Ring => {
+                let radius = point.length();
+                if !self.has_room_for_ring(radius) {
+                    return;
+                }
+                let role = self
+                    .level
+                    .rings
+                    .iter()
+                    .min_by(|a, b| {
+                        let distance = |ring: &Ring| (ring.radius - radius).abs();
+                        distance(a)
+                            .partial_cmp(&distance(b))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .map_or(PaddleRole::Outer, |ring| ring.role);
+                let ring = Ring {
+                    role,
+                    radius,
+                    paddle_height: 4.0,
+                    paddle_arc: PI / 6.0,
+                    track: None,
+                    offset: 0.0,
+                    points: 1,
+                };
+                if !self.keeps_valid(|level| level.rings.push(ring)) {
+                    return;
+                }
+                self.select_added(Target::Ring(self.level.rings.len() - 1));
+            }
+            Tool::Spawn => {
+                // counterclockwise
+                let direction = Vec2::new(-point.y, point.x).normalize();
+                self.level.spawns.push(Spawn {
+                    position: point,
+                    direction,
+                });
+                self.select_added(Target::Spawn(self.level.spawns.len() - 1));
+            }
+        }
+    }
+
+    /// A new ring of `radius` would be far enough from the other rings (and from the center).
+    fn has_room_for_ring(&self, radius: f32) -> bool {
+        radius >= MIN_RING_GAP
+            && self.level.rings.iter().all(|ring| {
+                let (min, max) = ring.radius_range();
+                radius - max >= MIN_RING_GAP || min - radius >= MIN_RING_GAP
+            })
+    }
+
+    /// Apply `edit` to the level, unless it makes a valid level invalid (eg a spawn no longer
+    /// between the rings).
+    fn keeps_valid(&mut self, edit: impl FnOnce(&mut Level)) -> bool {
+        let before = self.level.clone();
+        edit(&mut self.level);
+        if before.validate().is_ok() && self.level.validate().is_err() {
+            self.level = before;
+            return false;
+        }
+        true
+    }
+
+    fn select_added(&mut self, target: Target) {
+        self.selected = Some(target);
+        self.dragging = Some((target, Vec2::zero()));
+        self.changed = true;
+    }
+
+    /// Move the element picked by the last `press` (if the button is still pressed).
+    pub fn drag_to(&mut self, point: Vec2) {
+        if let Some((target, offset)) = self.dragging {
+            self.move_to(target, point + offset);
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.dragging = None;
+    }
+
+    /// The point of the element that follows the pointer, `None` for a ring (its radius follows the pointer).
+    fn position_of(&self, target: Target) -> Option<Vec2> {
+        match target {
+            Target::Ring(_) => None,
+            Target::Obstacle(index) => Some(match self.level.obstacles[index].shape {
+                Shape::Bumper { center, .. } => center,
+                Shape::Deflector { start, end } => (start + end) / 2.0,
+            }),
+            Target::Pad { teleporter, pad } => {
+                Some(self.level.teleporters[teleporter].pads[pad].center)
+            }
+            Target::Spawn(index) => Some(self.level.spawns[index].position),
+        }
+    }
+
+    fn move_to(&mut self, target: Target, point: Vec2) {
+        match target {
+            Target::Ring(index) => {
+                // the rings stay in the same order
+                let (inside, outside) = self.neighbour_radii(index);
+                let ring = &mut self.level.rings[index];
+                // the radius that puts the ring on the point (a track is scaled)
+                let wanted = match ring.track {
+                    Some(_) => point.length() * ring.radius / ring.radius_at(point),
+                    None => point.length(),
+                };
+                let radius = wanted
+                    .max(inside.unwrap_or(0.0) + MIN_RING_GAP)
+                    .min(outside.map_or(f32::MAX, |outside| outside - MIN_RING_GAP));
+                if let Some(track) = ring.track.as_mut() {
+                    *track = track.scaled(radius / ring.radius);
+                }
+                ring.radius = radius;
+            }
+            Target::Obstacle(index) => match &mut self.level.obstacles[index].shape {
+                Shape::Bumper { center, .. } => *center = point,
+                Shape::Deflector { start, end } => {
+                    let translation = point - (*start + *end) / 2.0;
+                    *start += translation;
+                    *end += translation;
+                }
+            },
+            Target::Pad { teleporter, pad } => {
+                self.level.teleporters[teleporter].pads[pad].center = point
+            }
+            Target::Spawn(index) => self.level.spawns[index].position = point,
+        }
+        self.changed = true;
+    }
+
+    /// Remove the selected element (not the last `Outer` or `Inner` ring, nor the last spawn). A
+    /// pad removes its teleporter.
+    pub fn delete_selected(&mut self) -> bool {
+        let removed = match self.selected {
+            Some(Target::Ring(index)) => {
+                let role = self.level.rings[index].role;
+                let with_role = self
+                    .level
+                    .rings
+                    .iter()
+                    .filter(|ring| ring.role == role)
+                    .count();
+                with_role > 1
+                    && self.keeps_valid(|level| {
+                        level.rings.remove(index);
+                    })
+            }
+            Some(Target::Obstacle(index)) => {
+                self.level.obstacles.remove(index);
+                true
+            }
+            Some(Target::Pad { teleporter, .. }) => {
+                self.level.teleporters.remove(teleporter);
+                true
+            }
+            Some(Target::Spawn(index)) if self.level.spawns.len() > 1 => {
+                self.level.spawns.remove(index);
+                true
+            }
+            _ => false,
+        };
+        if removed {
+            self.selected = None;
+            self.dragging = None;
+            self.changed = true;
+        }
+        removed
+    }
+
+    /// Grow (or shrink with a negative `delta`) the selected element: the thickness of the paddle
+    /// of a ring, the radius of a bumper or a pad, the length of a deflector.
+    pub fn resize_selected(&mut self, delta: f32) {
+        match self.selected {
+            Some(Target::Ring(index)) => {
+                let ring = &mut self.level.rings[index];
+                ring.paddle_height = (ring.paddle_height + delta).max(1.0);
+            }
+            Some(Target::Obstacle(index)) => match &mut self.level.obstacles[index].shape {
+                Shape::Bumper { radius, .. } => *radius = (*radius + delta).max(2.0),
+                Shape::Deflector { start, end } => {
+                    let direction = (*end - *start).normalize();
+                    let length = (*end - *start).length();
+                    let grow = (length + delta).max(4.0) - length;
+                    *start -= direction * (grow / 2.0);
+                    *end += direction * (grow / 2.0);
+                }
+            },
+            Some(Target::Pad { teleporter, pad }) => {
+                let pad = &mut self.level.teleporters[teleporter].pads[pad];
+                pad.radius = (pad.radius + delta).max(4.0);
+            }
+            Some(Target::Spawn(_)) | None => return,
+        }
+        self.changed = true;
+    }
+
+    /// Turn the selected element counterclockwise by `angle` (radian): widen the arc of the paddle
+    /// of a ring, turn a pad, a deflector (around its middle) or the direction of a spawn.
+    pub fn rotate_selected(&mut self, angle: f32) {
+        let (sin, cos) = angle.sin_cos();
+        let rotate = |v: Vec2| Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos);
+        match self.selected {
+            Some(Target::Ring(index)) => {
+                let ring = &mut self.level.rings[index];
+                ring.paddle_arc = (ring.paddle_arc + angle).clamp(0.05, 2.0 * PI - 0.05);
+            }
+            Some(Target::Obstacle(index)) => match &mut self.level.obstacles[index].shape {
+                Shape::Bumper { .. } => return,
+                Shape::Deflector { start, end } => {
+                    let middle = (*start + *end) / 2.0;
+                    *start = middle + rotate(*start - middle);
+                    *end = middle + rotate(*end - middle);
+                }
+            },
+            Some(Target::Pad { teleporter, pad }) => {
+                self.level.teleporters[teleporter].pads[pad].angle += angle;
+            }
+            Some(Target::Spawn(index)) => {
+                let spawn = &mut self.level.spawns[index];
+                spawn.direction = rotate(spawn.direction);
+            }
+            None => return,
+        }
+        self.changed = true;
+    }
+
+    /// A circle around the selected element (center, radius), to highlight it.
+    pub fn selection_outline(&self) -> Option<(Vec2, f32)> {
+        let margin = 4.0;
+        match self.selected? {
+            Target::Ring(index) => Some((Vec2::zero(), self.level.rings[index].radius)),
+            Target::Obstacle(index) => Some(match self.level.obstacles[index].shape {
+                Shape::Bumper { center, radius } => (center, radius + margin),
+                Shape::Deflector { start, end } => {
+                    ((start + end) / 2.0, (end - start).length() / 2.0 + margin)
+                }
+            }),
+            Target::Pad { teleporter, pad } => {
+                let pad = &self.level.teleporters[teleporter].pads[pad];
+                Some((pad.center, pad.radius + margin))
+            }
+            Target::Spawn(index) => Some((self.level.spawns[index].position, PICK_DISTANCE)),
+        }
+    }
+
+    /// A line about the selected element (for the UI).
+    pub fn describe_selected(&self) -> String {
+        match self.selected {
+            None => "nothing selected".to_string(),
+            Some(Target::Ring(index)) => {
+                let ring = &self.level.rings[index];
+                format!(
+                    "{:?} ring: radius {:.0}, paddle arc {:.0}°, thickness {:.0}",
+                    ring.role,
+                    ring.radius,
+                    ring.paddle_arc.to_degrees(),
+                    ring.paddle_height
+                )
+            }
+            Some(Target::Obstacle(index)) => match self.level.obstacles[index].shape {
+                Shape::Bumper { center, radius } => format!(
+                    "bumper #{}: ({:.0}, {:.0}), radius {:.0}",
+                    index, center.x, center.y, radius
+                ),
+                Shape::Deflector { start, end } => format!(
+                    "deflector #{}: ({:.0}, {:.0}) -> ({:.0}, {:.0})",
+                    index, start.x, start.y, end.x, end.y
+                ),
+            },
+            Some(Target::Pad { teleporter, pad }) => {
+                let pad_ref = &self.level.teleporters[teleporter].pads[pad];
+                format!(
+                    "teleporter #{} pad {}: ({:.0}, {:.0}), radius {:.0}, angle {:.0}°",
+                    teleporter,
+                    pad + 1,
+                    pad_ref.center.x,
+                    pad_ref.center.y,
+                    pad_ref.radius,
+                    pad_ref.angle.to_degrees()
+                )
+            }
+            Some(Target::Spawn(index)) => {
+                let spawn = &self.level.spawns[index];
+                format!(
+                    "spawn #{}: ({:.0}, {:.0}), direction {:.0}°",
+                    index,
+                    spawn.position.x,
+                    spawn.position.y,
+                    spawn.direction.y.atan2(spawn.direction.x).to_degrees()
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{RADIUS_EXTERN, RADIUS_INTERN};
+
+    fn editor() -> LevelEditor {
+        LevelEditor::new(Level {
+            obstacles: vec![],
+            teleporters: vec![],
+            ..Level::default()
+        })
+    }
+
+    #[test]
+    fn test_drag_a_ring() {
+        let mut editor = editor();
+        editor.press(Vec2::new(0.0, RADIUS_EXTERN + 3.0));
+        assert_eq!(editor.selected, Some(Target::Ring(0)));
+        editor.drag_to(Vec2::new(250.0, 0.0));
+        editor.release();
+        assert_eq!(editor.level.rings[0].radius, 250.0);
+        assert!(editor.take_changed());
+        assert!(!editor.take_changed());
+        // the inner ring stays inside the outer ring
+        editor.press(Vec2::new(-RADIUS_INTERN, 0.0));
+        assert_eq!(editor.selected, Some(Target::Ring(1)));
+        editor.drag_to(Vec2::new(-400.0, 0.0));
+        assert_eq!(editor.level.rings[1].radius, 250.0 - MIN_RING_GAP);
+        editor.drag_to(Vec2::new(-120.0, 0.0));
+        editor.release();
+        assert_eq!(editor.level.rings[1].radius, 120.0);
+        // the arc and the thickness of its paddle
+        editor.rotate_selected(0.5);
+        editor.resize_selected(2.0);
+        assert!(
+            (editor.level.rings[1].paddle_arc - (std::f32::consts::FRAC_PI_6 + 0.5)).abs() < 1e-5
+        );
+        assert_eq!(editor.level.rings[1].paddle_height, 6.0);
+        assert!(editor.level.validate().is_ok());
+    }
+
+    #[test]
+    fn test_add_and_move_elements() {
+        let mut editor = editor();
+        editor.tool = Tool::Bumper;
+        editor.press(Vec2::new(150.0, 0.0));
+        editor.drag_to(Vec2::new(160.0, 10.0));
+        editor.release();
+        assert_eq!(
+            editor.level.obstacles[0].shape,
+            Shape::Bumper {
+                center: Vec2::new(160.0, 10.0),
+                radius: 10.0
+            }
+        );
+        // outside of the arena
+        editor.press(Vec2::new(350.0, 0.0));
+        assert_eq!(editor.level.obstacles.len(), 1);
+
+        editor.tool = Tool::Teleporter;
+        editor.press(Vec2::new(0.0, 200.0));
+        assert!(editor.level.teleporters.is_empty());
+        editor.press(Vec2::new(0.0, -200.0));
+        editor.release();
+        assert_eq!(editor.level.teleporters.len(), 1);
+        assert_eq!(editor.pending_pad, None);
+
+        editor.tool = Tool::Spawn;
+        editor.press(Vec2::new(-200.0, 0.0));
+        editor.release();
+        assert_eq!(editor.level.spawns.len(), 2);
+        assert!(editor.level.validate().is_ok());
+
+        // pick and drag with an offset
+        editor.tool = Tool::Select;
+        editor.press(Vec2::new(165.0, 10.0));
+        assert_eq!(editor.selected, Some(Target::Obstacle(0)));
+        editor.drag_to(Vec2::new(175.0, 10.0));
+        assert_eq!(
+            editor.level.obstacles[0].shape,
+            Shape::Bumper {
+                center: Vec2::new(170.0, 10.0),
+                radius: 10.0
+            }
+        );
+        assert!(editor.delete_selected());
+        assert!(editor.level.obstacles.is_empty());
+        // a pad removes its teleporter
+        editor.press(Vec2::new(0.0, 205.0));
+        assert!(matches!(editor.selected, Some(Target::Pad { .. })));
+        assert!(editor.delete_selected());
+        assert!(editor.level.teleporters.is_empty());
+        // not the last spawn
+        editor.press(Vec2::new(-200.0, 0.0));
+        assert!(editor.delete_selected());
+        editor.press(Spawn::default().position);
+        assert_eq!(editor.selected, Some(Target::Spawn(0)));
+        assert!(!editor.delete_selected());
+
+        // a ring between the rings, driven by the player of the nearest ring
+        editor.tool = Tool::Bumper;
+        editor.press(Vec2::new(0.0, -255.0));
+        editor.release();
+        editor.tool = Tool::Ring;
+        editor.press(Vec2::new(0.0, 240.0));
+        editor.release();
+        assert_eq!(editor.selected, Some(Target::Ring(2)));
+        let ring = &editor.level.rings[2];
+        assert_eq!((ring.role, ring.radius), (PaddleRole::Outer, 240.0));
+        editor.rotate_selected(0.5);
+        editor.resize_selected(2.0);
+        assert_eq!(editor.level.rings[2].paddle_height, 6.0);
+        // too close to an other ring
+        editor.press(Vec2::new(0.0, 250.0));
+        editor.press(Vec2::new(0.0, RADIUS_INTERN + 5.0));
+        assert_eq!(editor.level.rings.len(), 3);
+        // under the inner ring, the new innermost ring
+        editor.press(Vec2::new(0.0, 60.0));
+        editor.release();
+        assert_eq!(editor.level.rings[3].role, PaddleRole::Inner);
+        assert!(editor.level.validate().is_ok());
+
+        editor.tool = Tool::Select;
+        // without the outermost ring, the bumper would be outside
+        editor.press(Vec2::new(RADIUS_EXTERN, 0.0));
+        assert_eq!(editor.selected, Some(Target::Ring(0)));
+        assert!(!editor.delete_selected());
+        assert_eq!(editor.level.rings.len(), 4);
+        editor.press(Vec2::new(240.0, 0.0));
+        assert!(editor.delete_selected());
+        editor.press(Vec2::new(60.0, 0.0));
+        assert!(editor.delete_selected());
+        assert_eq!(editor.level.rings.len(), 2);
+        // not the last Inner ring
+        editor.press(Vec2::new(RADIUS_INTERN, 0.0));
+        assert_eq!(editor.selected, Some(Target::Ring(1)));
+        assert!(!editor.delete_selected());
+        assert!(editor.level.validate().is_ok());
+    }
+}
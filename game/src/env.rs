@@ -0,0 +1,413 @@
+//! Reinforcement-learning style environment: an agent drives the paddles step by step and
+//! receives what it observes, its reward and whether the episode is done.
+//!
+//! The protocol is line-delimited json, one response per request:
+//!
+//! ```text
+//! > {"cmd":"reset","seed":42}
+//! < {"observation":{...},"reward":0.0,"done":false,"tick":0,"score":0}
+//! > {"cmd":"step","outer_angle":4.2,"inner_angle":null}
+//! < {"observation":{...},"reward":1.0,"done":false,"tick":1,"score":1}
+//! ```
+//!
+//! `reset` also accepts optional `difficulty` ("Easy", "Normal", "Hard"), `play_mode` ("Solo", "Coop", "Versus")
+//! `power_ups` (`true` to spawn power-up zones), `obstacles` (`true` for bumpers, deflectors and teleporters)
+//! `multiball` (`true` for extra balls) and `level` (a `Level`, as json, instead of the default arena,
+//! with its own obstacles and teleporters: `obstacles` is ignored).
+
+use crate::level::Level;
+use crate::multiball::Multiball;
+use crate::obstacle::{default_obstacles, Shape};
+use crate::paddle::PaddleRole;
+use crate::play_mode::PlayMode;
+use crate::power_up::{Effect, PowerUpKind};
+use crate::simulation::{Event, Inputs, Simulation};
+use crate::speed_curve::Difficulty;
+use crate::teleporter::{default_teleporters, Teleporter};
+use crate::timestep::FixedTimestep;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum EnvRequest {
+    Reset {
+        seed: u64,
+        #[serde(default)]
+        difficulty: Difficulty,
+        #[serde(default)]
+        play_mode: PlayMode,
+        #[serde(default)]
+        power_ups: bool,
+        #[serde(default)]
+        obstacles: bool,
+        #[serde(default)]
+        multiball: bool,
+        #[serde(default)]
+        level: Option<Box<Level>>,
+    },
+    /// angles in radian, `null` (or missing) to keep the paddle where it is,
+    /// `true` to release the balls caught by a magnetic paddle
+    Step {
+        #[serde(default)]
+        inner_angle: Option<f32>,
+        #[serde(default)]
+        outer_angle: Option<f32>,
+        #[serde(default)]
+        inner_release: bool,
+        #[serde(default)]
+        outer_release: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BallObservation {
+    pub position: [f32; 2],
+    /// units per second
+    pub velocity: [f32; 2],
+    /// caught by a magnetic paddle
+    pub attached: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PaddleObservation {
+    pub role: PaddleRole,
+    pub radius: f32,
+    pub angle: f32,
+    pub half_surface_angle: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ZoneObservation {
+    pub kind: PowerUpKind,
+    pub position: [f32; 2],
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Observation {
+    pub balls: Vec<BallObservation>,
+    pub paddles: Vec<PaddleObservation>,
+    pub zones: Vec<ZoneObservation>,
+    pub effects: Vec<Effect>,
+    /// shapes of the obstacles at the current step
+    pub obstacles: Vec<Shape>,
+    pub teleporters: Vec<Teleporter>,
+}
+
+impl Observation {
+    pub fn of(sim: &Simulation) -> Self {
+        Observation {
+            balls: sim
+                .balls
+                .iter()
+                .map(|ball| {
+                    let velocity = if ball.attached.is_some() {
+                        Vec2::zero()
+                    } else {
+                        ball.mvt_dir * ball.velocity(&sim.speed_curve)
+                    };
+                    BallObservation {
+                        position: [ball.position.x, ball.position.y],
+                        velocity: [velocity.x, velocity.y],
+                        attached: ball.attached.is_some(),
+                    }
+                })
+                .collect(),
+            paddles: sim
+                .paddles
+                .iter()
+                .map(|paddle| PaddleObservation {
+                    role: paddle.role,
+                    radius: paddle.radius_origin,
+                    angle: paddle.angle_origin,
+                    half_surface_angle: paddle.half_surface_angle,
+                })
+                .collect(),
+            zones: sim
+                .power_ups
+                .zones
+                .iter()
+                .map(|zone| ZoneObservation {
+                    kind: zone.kind,
+                    position: [zone.position.x, zone.position.y],
+                    radius: zone.radius,
+                })
+                .collect(),
+            effects: sim.power_ups.effects.clone(),
+            obstacles: sim
+                .obstacles
+                .iter()
+                .map(|obstacle| obstacle.shape_now())
+                .collect(),
+            teleporters: sim.teleporters.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum EnvResponse {
+    Step {
+        observation: Observation,
+        /// score gained by the step
+        reward: f32,
+        /// the last ball in play escaped, the episode should be reset
+        done: bool,
+        tick: u64,
+        score: usize,
+    },
+    Error {
+        error: String,
+    },
+}
+
+/// The simulation driven by an agent.
+pub struct Env {
+    sim: Option<Simulation>,
+    done: bool,
+    step_seconds: f32,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Env {
+            sim: None,
+            done: false,
+            step_seconds: FixedTimestep::default().step_seconds,
+        }
+    }
+}
+
+impl Env {
+    pub fn handle(&mut self, request: EnvRequest) -> EnvResponse {
+        match request {
+            EnvRequest::Reset {
+                seed,
+                difficulty,
+                play_mode,
+                power_ups,
+                obstacles,
+                multiball,
+                level,
+            } => {
+                let mut sim = Simulation::new();
+                sim.seed = seed;
+                sim.speed_curve = difficulty.speed_curve();
+                if let Some(level) = level.as_ref() {
+                    if let Err(err) = level.validate() {
+                        return EnvResponse::Error {
+                            error: err.to_string(),
+                        };
+                    }
+                    level.apply(&mut sim, difficulty);
+                }
+                sim.play_mode = play_mode;
+                sim.power_ups.enabled = power_ups;
+                // a level has its own obstacles
+                if obstacles && level.is_none() {
+                    sim.obstacles = default_obstacles();
+                    sim.teleporters = default_teleporters();
+                }
+                sim.multiball = Multiball {
+                    enabled: multiball,
+                    ..Multiball::default()
+                };
+                sim.start();
+                self.done = false;
+                let response = response(&sim, 0.0, self.done);
+                self.sim = Some(sim);
+                response
+            }
+            EnvRequest::Step {
+                inner_angle,
+                outer_angle,
+                inner_release,
+                outer_release,
+            } => {
+                if self.done {
+                    return EnvResponse::Error {
+                        error: "the episode is done, reset it".to_string(),
+                    };
+                }
+                let sim = match self.sim.as_mut() {
+                    Some(sim) => sim,
+                    None => {
+                        return EnvResponse::Error {
+                            error: "no episode, reset first".to_string(),
+                        }
+                    }
+                };
+                let score = sim.scoreboard.score;
+                let in_play: Vec<_> = sim.balls.iter().map(|ball| ball.id).collect();
+                let inputs = Inputs {
+                    outer_angle,
+                    inner_angle,
+                    outer_release,
+                    inner_release,
+                };
+                let events = sim.step(self.step_seconds, &inputs);
+                // in versus, a new ball is served when the last one escapes
+                let rally_over = events.iter().any(|e| matches!(e, Event::Escaped { .. }))
+                    && !sim.balls.iter().any(|ball| in_play.contains(&ball.id));
+                self.done = rally_over || events.iter().any(|e| matches!(e, Event::GameOver));
+                let reward = (sim.scoreboard.score - score) as f32;
+                response(sim, reward, self.done)
+            }
+        }
+    }
+
+    /// Handle the requests (one json per line) from `input` until its end, write the responses to `output`.
+    pub fn serve<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<EnvRequest>(&line) {
+                Ok(request) => self.handle(request),
+                Err(err) => EnvResponse::Error {
+                    error: format!("invalid request: {}", err),
+                },
+            };
+            serde_json::to_writer(&mut output, &response)?;
+            writeln!(output)?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn response(sim: &Simulation, reward: f32, done: bool) -> EnvResponse {
+    EnvResponse::Step {
+        observation: Observation::of(sim),
+        reward,
+        done,
+        tick: sim.tick,
+        score: sim.scoreboard.score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obstacle::Obstacle;
+
+    fn serve(requests: &str) -> Vec<serde_json::Value> {
+        let mut output = vec![];
+        Env::default()
+            .serve(requests.as_bytes(), &mut output)
+            .unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_protocol() {
+        let responses = serve(
+            r#"{"cmd":"step"}
+{"cmd":"reset","seed":0}
+{"cmd":"step","outer_angle":-1.29,"inner_angle":null}
+not json
+"#,
+        );
+        assert_eq!(responses.len(), 4);
+        assert!(responses[0]["error"].is_string());
+        assert_eq!(responses[1]["done"], false);
+        assert_eq!(
+            responses[1]["observation"]["balls"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(responses[1]["observation"]["paddles"][0]["role"], "Outer");
+        assert_eq!(responses[2]["tick"], 1);
+        assert!(
+            (responses[2]["observation"]["paddles"][0]["angle"]
+                .as_f64()
+                .unwrap()
+                - (2.0 * std::f64::consts::PI - 1.29))
+                .abs()
+                < 1e-3
+        );
+        assert!(responses[3]["error"].is_string());
+    }
+
+    #[test]
+    fn test_reset_with_an_invalid_level() {
+        let responses = serve(
+            r#"{"cmd":"reset","seed":0,"level":{"rings":[],"spawns":[]}}
+"#,
+        );
+        assert_eq!(responses[0]["error"], "invalid level: no Outer ring");
+    }
+
+    #[test]
+    fn test_reset_with_a_level() {
+        let level = Level {
+            obstacles: vec![Obstacle::bumper(Vec2::new(200.0, 0.0), 10.0)],
+            teleporters: vec![],
+            ..Level::default()
+        };
+        let mut env = Env::default();
+        let response = env.handle(EnvRequest::Reset {
+            seed: 0,
+            difficulty: Difficulty::Normal,
+            play_mode: PlayMode::Solo,
+            power_ups: false,
+            obstacles: true,
+            multiball: false,
+            level: Some(Box::new(level)),
+        });
+        match response {
+            EnvResponse::Step { observation, .. } => {
+                assert_eq!(
+                    observation.obstacles,
+                    vec![Shape::Bumper {
+                        center: Vec2::new(200.0, 0.0),
+                        radius: 10.0
+                    }]
+                );
+                assert!(observation.teleporters.is_empty());
+            }
+            EnvResponse::Error { error } => panic!("{}", error),
+        }
+    }
+
+    #[test]
+    fn test_episode_until_done() {
+        let mut env = Env::default();
+        env.handle(EnvRequest::Reset {
+            seed: 0,
+            difficulty: Difficulty::Normal,
+            play_mode: PlayMode::Solo,
+            power_ups: false,
+            obstacles: false,
+            multiball: false,
+            level: None,
+        });
+        // the paddles are on the opposite side of the ball
+        let step = EnvRequest::Step {
+            inner_angle: Some(0.5 * std::f32::consts::PI),
+            outer_angle: Some(0.5 * std::f32::consts::PI),
+            inner_release: false,
+            outer_release: false,
+        };
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            match env.handle(step.clone()) {
+                EnvResponse::Step { done: true, .. } => break,
+                EnvResponse::Step { reward, .. } => assert_eq!(reward, 0.0),
+                EnvResponse::Error { error } => panic!("{}", error),
+            }
+        }
+        assert!(steps < 120);
+        assert!(matches!(env.handle(step), EnvResponse::Error { .. }));
+    }
+}
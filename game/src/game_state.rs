@@ -0,0 +1,133 @@
+/// Duration of the countdown before the ball starts to move.
+pub const COUNTDOWN_SECONDS: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GameState {
+    #[default]
+    Title,
+    /// the round is ready (ball spawned), but nothing moves until the end of the countdown
+    Countdown {
+        remaining: f32,
+    },
+    Playing,
+    Paused,
+    GameOver,
+    /// the level is edited (see `LevelEditor`), nothing moves
+    Editing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStateEvent {
+    Start,
+    TogglePause,
+    GameOver,
+    BackToTitle,
+    /// enter / leave the level editor
+    Edit,
+    /// play the edited level ("play from here")
+    Play,
+}
+
+impl GameState {
+    /// The state after the `event`, `None` if the event is ignored in the current state.
+    pub fn on_event(&self, event: GameStateEvent) -> Option<GameState> {
+        match (self, event) {
+            (GameState::Title, GameStateEvent::Start)
+            | (GameState::GameOver, GameStateEvent::Start) => Some(GameState::Countdown {
+                remaining: COUNTDOWN_SECONDS,
+            }),
+            (GameState::Playing, GameStateEvent::TogglePause) => Some(GameState::Paused),
+            (GameState::Paused, GameStateEvent::TogglePause) => Some(GameState::Playing),
+            (GameState::Playing, GameStateEvent::GameOver) => Some(GameState::GameOver),
+            (GameState::Paused, GameStateEvent::BackToTitle)
+            | (GameState::GameOver, GameStateEvent::BackToTitle)
+            | (GameState::Editing, GameStateEvent::BackToTitle)
+            | (GameState::Editing, GameStateEvent::Edit) => Some(GameState::Title),
+            (GameState::Title, GameStateEvent::Edit)
+            | (GameState::GameOver, GameStateEvent::Edit) => Some(GameState::Editing),
+            (GameState::Editing, GameStateEvent::Play) => Some(GameState::Countdown {
+                remaining: COUNTDOWN_SECONDS,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The state after `delta_seconds` (only the countdown changes with the time).
+    pub fn tick(&self, delta_seconds: f32) -> GameState {
+        match self {
+            GameState::Countdown { remaining } if *remaining <= delta_seconds => GameState::Playing,
+            GameState::Countdown { remaining } => GameState::Countdown {
+                remaining: remaining - delta_seconds,
+            },
+            other => *other,
+        }
+    }
+
+    /// Balls move, paddles score only while playing.
+    pub fn is_running(&self) -> bool {
+        *self == GameState::Playing
+    }
+
+    /// Paddles can be moved while playing and during the countdown (to get ready).
+    pub fn accepts_paddle_inputs(&self) -> bool {
+        matches!(self, GameState::Playing | GameState::Countdown { .. })
+    }
+
+    /// 2 states of the same kind (eg `Countdown` with different remaining time) share the same UI.
+    pub fn same_kind(&self, other: &GameState) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_lifecycle() {
+        let state = GameState::default();
+        assert_eq!(state.on_event(GameStateEvent::TogglePause), None);
+        let state = state.on_event(GameStateEvent::Start).unwrap();
+        assert!(!state.is_running());
+        assert!(state.accepts_paddle_inputs());
+        let state = state.tick(1.0);
+        assert_eq!(state, GameState::Countdown { remaining: 2.0 });
+        let state = state.tick(2.5);
+        assert!(state.is_running());
+        // no restart while playing
+        assert_eq!(state.on_event(GameStateEvent::Start), None);
+        let state = state.on_event(GameStateEvent::TogglePause).unwrap();
+        assert!(!state.is_running());
+        assert!(!state.accepts_paddle_inputs());
+        assert_eq!(state.tick(10.0), GameState::Paused);
+        let state = state.on_event(GameStateEvent::TogglePause).unwrap();
+        let state = state.on_event(GameStateEvent::GameOver).unwrap();
+        assert_eq!(state, GameState::GameOver);
+        assert_eq!(
+            state.on_event(GameStateEvent::BackToTitle),
+            Some(GameState::Title)
+        );
+        assert!(matches!(
+            state.on_event(GameStateEvent::Start),
+            Some(GameState::Countdown { .. })
+        ));
+    }
+
+    #[test]
+    fn test_edit_and_play_from_here() {
+        let state = GameState::Title.on_event(GameStateEvent::Edit).unwrap();
+        assert_eq!(state, GameState::Editing);
+        assert!(!state.accepts_paddle_inputs());
+        // the clicks of the editor don't start a round
+        assert_eq!(state.on_event(GameStateEvent::Start), None);
+        assert_eq!(state.on_event(GameStateEvent::Edit), Some(GameState::Title));
+        let state = state.on_event(GameStateEvent::Play).unwrap();
+        assert!(matches!(state, GameState::Countdown { .. }));
+        // back to the editor after the round
+        assert_eq!(state.on_event(GameStateEvent::Edit), None);
+        assert_eq!(
+            GameState::GameOver.on_event(GameStateEvent::Edit),
+            Some(GameState::Editing)
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use crate::simulation::{Escape, Event, Inputs, Simulation};
+use serde::Serialize;
+
+/// Summary of a round simulated without window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunReport {
+    pub seed: u64,
+    /// number of steps simulated
+    pub ticks: u64,
+    pub score: usize,
+    pub hits: usize,
+    pub max_velocity: f32,
+    /// most balls in play at the same time (see `Multiball`)
+    pub max_balls: usize,
+    /// how the last ball escaped, `None` if the round was still running after the last tick
+    pub escape: Option<Escape>,
+    pub game_over: bool,
+}
+
+/// Step the (started) `sim` until the game over or `max_ticks` steps, with the inputs returned by
+/// `inputs_for` before each step.
+pub fn run_headless<F>(
+    sim: &mut Simulation,
+    step_seconds: f32,
+    max_ticks: u64,
+    mut inputs_for: F,
+) -> RunReport
+where
+    F: FnMut(&Simulation) -> Inputs,
+{
+    let mut report = RunReport {
+        seed: sim.seed,
+        ticks: 0,
+        score: 0,
+        hits: 0,
+        max_velocity: max_velocity(sim),
+        max_balls: sim.balls.len(),
+        escape: None,
+        game_over: false,
+    };
+    while report.ticks < max_ticks && !report.game_over {
+        let inputs = inputs_for(sim);
+        for event in sim.step(step_seconds, &inputs) {
+            match event {
+                Event::Hit { .. } => report.hits += 1,
+                Event::Escaped { escape, .. } => report.escape = Some(escape),
+                Event::GameOver => report.game_over = true,
+                Event::Point { .. }
+                | Event::Bounced { .. }
+                | Event::Teleported { .. }
+                | Event::Collected { .. }
+                | Event::Caught { .. }
+                | Event::Released { .. }
+                | Event::Split { .. }
+                | Event::Collided { .. } => (),
+            }
+        }
+        report.ticks += 1;
+        report.max_velocity = report.max_velocity.max(max_velocity(sim));
+        report.max_balls = report.max_balls.max(sim.balls.len());
+    }
+    if !report.game_over {
+        report.escape = None;
+    }
+    report.score = sim.scoreboard.score;
+    report
+}
+
+fn max_velocity(sim: &Simulation) -> f32 {
+    sim.balls
+        .iter()
+        .map(|ball| ball.velocity(&sim.speed_curve))
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Bot;
+    use crate::controller::controlled_inputs;
+
+    #[test]
+    fn test_run_until_game_over() {
+        let mut sim = Simulation::new();
+        sim.start();
+        let report = run_headless(&mut sim, 1.0 / 120.0, 10_000, |sim| {
+            controlled_inputs(&mut Bot::Idle, sim)
+        });
+        assert!(report.game_over);
+        assert_eq!(report.escape, Some(Escape::Outside));
+        assert!(report.ticks < 10_000);
+        assert_eq!(report.max_velocity, 410.0);
+        assert_eq!(report.max_balls, 1);
+
+        let mut sim = Simulation::new();
+        sim.seed = 3;
+        sim.start();
+        let report = run_headless(&mut sim, 1.0 / 120.0, 600, |sim| {
+            controlled_inputs(&mut Bot::Follow, sim)
+        });
+        assert!(report.hits > 0);
+        assert_eq!(report.score, report.hits);
+        assert!(report.max_velocity > 410.0);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::play_mode::PlayMode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Number of entries kept in the `HighScores` table.
+pub const HIGH_SCORES_LEN: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub score: usize,
+    /// end of the round, in seconds since the unix epoch (UTC)
+    pub date: u64,
+    pub mode: PlayMode,
+    pub seed: u64,
+}
+
+impl fmt::Display for HighScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>5}  {}  {:?}",
+            self.score,
+            format_date(self.date),
+            self.mode
+        )
+    }
+}
+
+/// The best rounds, best first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    pub fn best(&self) -> usize {
+        self.entries.first().map(|entry| entry.score).unwrap_or(0)
+    }
+
+    /// Insert the `entry` at its rank, return the rank (0 is the best) or `None` if the entry is not
+    /// good enough to be in the table.
+    ///
+    /// On equal scores, the oldest entry stays ahead.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        let rank = self
+            .entries
+            .iter()
+            .position(|existing| existing.score < entry.score)
+            .unwrap_or(self.entries.len());
+        if rank >= HIGH_SCORES_LEN {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(HIGH_SCORES_LEN);
+        Some(rank)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<HighScores> {
+        let mut high_scores: HighScores = serde_json::from_str(json)?;
+        // the file could have been edited by hand
+        high_scores
+            .entries
+            .sort_by(|a, b| b.score.cmp(&a.score).then(a.date.cmp(&b.date)));
+        high_scores.entries.truncate(HIGH_SCORES_LEN);
+        Ok(high_scores)
+    }
+}
+
+/// `YYYY-MM-DD` of a unix timestamp (UTC).
+pub fn format_date(unix_seconds: u64) -> String {
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = (unix_seconds / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: usize, date: u64) -> HighScore {
+        HighScore {
+            score,
+            date,
+            mode: PlayMode::Solo,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn test_insert_keeps_the_top_n() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.best(), 0);
+        for i in 0..HIGH_SCORES_LEN {
+            assert_eq!(high_scores.insert(entry(10 + i, i as u64)), Some(0));
+        }
+        assert_eq!(high_scores.best(), 10 + HIGH_SCORES_LEN - 1);
+        assert_eq!(high_scores.insert(entry(5, 100)), None);
+        // the oldest keeps the rank on equal score
+        assert_eq!(high_scores.insert(entry(15, 100)), Some(5));
+        assert_eq!(high_scores.entries.len(), HIGH_SCORES_LEN);
+        assert_eq!(high_scores.entries.last().unwrap().score, 11);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(entry(3, 1_600_000_000));
+        high_scores.insert(HighScore {
+            score: 7,
+            date: 1_600_000_100,
+            mode: PlayMode::Coop,
+            seed: 42,
+        });
+        let json = high_scores.to_json().unwrap();
+        assert_eq!(HighScores::from_json(&json).unwrap(), high_scores);
+        assert!(HighScores::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        // Ludum Dare 47 (2020-10-02T22:00:00Z)
+        assert_eq!(format_date(1_601_676_000), "2020-10-02");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+    }
+}
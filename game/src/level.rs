@@ -0,0 +1,628 @@
+//! Levels: the rings (and their paddles), the obstacles, the teleporters, where the balls are
+//! served, the speed curve and the win conditions, described in a RON file.
+//!
+//! ```text
+//! Level(
+//!     name: "default",
+//!     rings: [
+//!         (role: Outer, radius: 285.0, paddle_height: 12.0, paddle_arc: 0.5235988),
+//!         (role: Inner, radius: 108.0, paddle_height: 4.0, paddle_arc: 0.5235988),
+//!     ],
+//!     spawns: [(position: (10.0, -196.5), direction: (0.70710677, -0.70710677))],
+//!     win: (score: Some(100)),
+//! )
+//! ```
+
+use crate::arena::ArenaMotion;
+use crate::obstacle::{default_obstacles, Obstacle, Shape};
+use crate::paddle::{Paddle, PaddleRole};
+use crate::play_mode::VERSUS_POINTS;
+use crate::simulation::{Simulation, RADIUS_EXTERN, RADIUS_INTERN};
+use crate::speed_curve::{Difficulty, SpeedCurve};
+use crate::spin::Spin;
+use crate::teleporter::{default_teleporters, Teleporter};
+use crate::track::{Track, TrackShape};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_6, PI};
+use std::fmt;
+
+/// A ring and the paddle that slides on it, the balls escape beyond the outermost ring or under
+/// the innermost ring.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ring {
+    pub role: PaddleRole,
+    /// with a `track`, the reference radius: the paddle covers the length `radius * paddle_arc`
+    pub radius: f32,
+    pub paddle_height: f32,
+    /// angle covered by the paddle (radian)
+    pub paddle_arc: f32,
+    /// the shape of the ring when it's not the circle of `radius`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track: Option<TrackShape>,
+    /// added to the angle requested by the player of `role` (radian)
+    #[serde(default)]
+    pub offset: f32,
+    /// points scored by a hit of the paddle
+    #[serde(default = "default_points")]
+    pub points: usize,
+}
+
+fn default_points() -> usize {
+    1
+}
+
+impl Ring {
+    /// The min and the max distance between the center and the ring.
+    pub fn radius_range(&self) -> (f32, f32) {
+        match self.track.as_ref() {
+            Some(shape) => Track::new(shape.clone()).radius_range(),
+            None => (self.radius, self.radius),
+        }
+    }
+
+    /// The distance between the center and the ring, in the direction of `point`.
+    pub fn radius_at(&self, point: Vec2) -> f32 {
+        match self.track.as_ref() {
+            Some(shape) => Track::new(shape.clone()).radius_at(point.y.atan2(point.x)),
+            None => self.radius,
+        }
+    }
+}
+
+/// Where and toward where a ball is served.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spawn {
+    pub position: Vec2,
+    /// normalized by `Level::apply`
+    pub direction: Vec2,
+}
+
+impl Default for Spawn {
+    /// The historical serve: between the rings, toward the bottom right.
+    fn default() -> Self {
+        Spawn {
+            position: Vec2::new(10.0, -(RADIUS_EXTERN + RADIUS_INTERN) / 2.0),
+            direction: Vec2::new(0.5, -0.5).normalize(),
+        }
+    }
+}
+
+/// How a round is won, without any the round only ends when the last ball escapes (solo, co-op).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WinConditions {
+    /// solo / co-op: the round is won at this score
+    #[serde(default)]
+    pub score: Option<usize>,
+    /// solo / co-op: the round is won after this duration (seconds) with a ball still inside
+    #[serde(default)]
+    pub survive_seconds: Option<f32>,
+    /// versus: the points to win the round
+    #[serde(default = "default_versus_points")]
+    pub versus_points: usize,
+}
+
+fn default_versus_points() -> usize {
+    VERSUS_POINTS
+}
+
+impl Default for WinConditions {
+    fn default() -> Self {
+        WinConditions {
+            score: None,
+            survive_seconds: None,
+            versus_points: VERSUS_POINTS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Level {
+    #[serde(default)]
+    pub name: String,
+    /// concentric rings, the outermost is an `Outer` ring and the innermost an `Inner` ring, the
+    /// rings between are driven by the player of their role
+    pub rings: Vec<Ring>,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(default)]
+    pub teleporters: Vec<Teleporter>,
+    /// a serve uses one of them (shuffled by the seed)
+    pub spawns: Vec<Spawn>,
+    /// `None` for the curve of the difficulty
+    #[serde(default)]
+    pub speed_curve: Option<SpeedCurve>,
+    #[serde(default)]
+    pub win: WinConditions,
+    /// the arena turns and pulses during the round
+    #[serde(default)]
+    pub motion: ArenaMotion,
+    /// how the paddles give their spin to the balls
+    #[serde(default)]
+    pub spin: Spin,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Parse(ron::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Parse(err) => write!(f, "failed to parse the level: {}", err),
+            LevelError::Invalid(msg) => write!(f, "invalid level: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<ron::Error> for LevelError {
+    fn from(err: ron::Error) -> Self {
+        LevelError::Parse(err)
+    }
+}
+
+impl Default for Level {
+    /// The arena of the game before the levels.
+    fn default() -> Self {
+        Level {
+            name: "default".to_string(),
+            rings: vec![
+                Ring {
+                    role: PaddleRole::Outer,
+                    radius: RADIUS_EXTERN,
+                    paddle_height: 12.0,
+                    paddle_arc: FRAC_PI_6,
+                    track: None,
+                    offset: 0.0,
+                    points: 1,
+                },
+                Ring {
+                    role: PaddleRole::Inner,
+                    radius: RADIUS_INTERN,
+                    paddle_height: 4.0,
+                    paddle_arc: FRAC_PI_6,
+                    track: None,
+                    offset: 0.0,
+                    points: 1,
+                },
+            ],
+            obstacles: default_obstacles(),
+            teleporters: default_teleporters(),
+            spawns: vec![Spawn::default()],
+            speed_curve: None,
+            win: WinConditions::default(),
+            motion: ArenaMotion::default(),
+            spin: Spin::default(),
+        }
+    }
+}
+
+impl Level {
+    /// Parse and validate a level.
+    pub fn from_ron(text: &str) -> Result<Level, LevelError> {
+        let level: Level = ron::de::from_str(text)?;
+        level.validate()?;
+        Ok(level)
+    }
+
+    pub fn to_ron(&self) -> Result<String, LevelError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    /// The level played by `sim` (eg to record it with a replay).
+    pub fn of(sim: &Simulation) -> Level {
+        Level {
+            name: String::new(),
+            rings: sim
+                .paddles
+                .iter()
+                .map(|paddle| Ring {
+                    role: paddle.role,
+                    radius: paddle.rest_radius,
+                    paddle_height: paddle.half_height * 2.0,
+                    paddle_arc: paddle.half_surface_angle * 2.0,
+                    track: paddle.track.as_ref().map(|track| track.shape.clone()),
+                    offset: paddle.offset,
+                    points: paddle.points,
+                })
+                .collect(),
+            obstacles: sim.obstacles.clone(),
+            teleporters: sim.teleporters.clone(),
+            spawns: sim.spawns.clone(),
+            speed_curve: Some(sim.speed_curve.clone()),
+            win: sim.win.clone(),
+            motion: ArenaMotion {
+                elapsed: 0.0,
+                ..sim.arena.clone()
+            },
+            spin: sim.spin.clone(),
+        }
+    }
+
+    /// Set up `sim` for the level, before the start of a round.
+    pub fn apply(&self, sim: &mut Simulation, difficulty: Difficulty) {
+        let mut rings: Vec<&Ring> = self.rings.iter().collect();
+        // the outer paddle first, like `Simulation::new`
+        rings.sort_by(|a, b| {
+            b.radius
+                .partial_cmp(&a.radius)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        sim.paddles = rings
+            .into_iter()
+            .map(|ring| {
+                let mut paddle =
+                    Paddle::new(ring.role, ring.radius, ring.paddle_height, ring.paddle_arc);
+                paddle.offset = ring.offset;
+                paddle.points = ring.points;
+                match ring.track.as_ref() {
+                    Some(shape) => paddle.with_track(shape.clone()),
+                    None => paddle,
+                }
+            })
+            .collect();
+        sim.obstacles = self.obstacles.clone();
+        sim.teleporters = self.teleporters.clone();
+        sim.spawns = self
+            .spawns
+            .iter()
+            .map(|spawn| Spawn {
+                position: spawn.position,
+                // normalizing twice may move the direction by an ulp (and a replay with it)
+                direction: if spawn.direction.is_normalized() {
+                    spawn.direction
+                } else {
+                    spawn.direction.normalize()
+                },
+            })
+            .collect();
+        sim.speed_curve = self
+            .speed_curve
+            .clone()
+            .unwrap_or_else(|| difficulty.speed_curve());
+        sim.win = self.win.clone();
+        sim.arena = self.motion.clone();
+        sim.spin = self.spin.clone();
+    }
+
+    /// Check what the format can't: the rings, the sizes, the spawns between the rings, ...
+    pub fn validate(&self) -> Result<(), LevelError> {
+        let invalid = |msg: String| Err(LevelError::Invalid(msg));
+        // false for NaN too
+        let positive = |value: f32| value > 0.0;
+        // the biggest `Outer` ring and the smallest `Inner` ring
+        let ring = |role: PaddleRole| {
+            let rings = self.rings.iter().filter(|ring| ring.role == role);
+            let by_radius = |a: &&Ring, b: &&Ring| {
+                a.radius
+                    .partial_cmp(&b.radius)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            };
+            match role {
+                PaddleRole::Outer => rings.max_by(by_radius),
+                PaddleRole::Inner => rings.min_by(by_radius),
+            }
+            .ok_or_else(|| LevelError::Invalid(format!("no {:?} ring", role)))
+        };
+        let outer = ring(PaddleRole::Outer)?;
+        let inner = ring(PaddleRole::Inner)?;
+        for ring in self.rings.iter() {
+            if !positive(ring.radius) {
+                return invalid(format!("radius of the {:?} ring should be > 0", ring.role));
+            }
+            if !positive(ring.paddle_height) {
+                return invalid(format!(
+                    "paddle_height of the {:?} ring should be > 0",
+                    ring.role
+                ));
+            }
+            if !(ring.paddle_arc > 0.0 && ring.paddle_arc < 2.0 * PI) {
+                return invalid(format!(
+                    "paddle_arc of the {:?} ring should be between 0 and 2π radian",
+                    ring.role
+                ));
+            }
+        }
+        for ring in self.rings.iter() {
+            if let Some(shape) = ring.track.as_ref() {
+                shape.validate().map_err(|msg| {
+                    LevelError::Invalid(format!("track of the {:?} ring: {}", ring.role, msg))
+                })?;
+            }
+        }
+        // the balls escape beyond the outermost ring and under the innermost ring
+        let outer_min = outer.radius_range().0;
+        let inner_max = inner.radius_range().1;
+        for ring in self
+            .rings
+            .iter()
+            .filter(|ring| ring.role != PaddleRole::Outer)
+        {
+            let max = ring.radius_range().1;
+            if max >= outer_min {
+                return invalid(format!(
+                    "the {:?} ring ({}) should be smaller than the Outer ring ({})",
+                    ring.role, max, outer_min
+                ));
+            }
+        }
+        for ring in self
+            .rings
+            .iter()
+            .filter(|ring| ring.role != PaddleRole::Inner)
+        {
+            let min = ring.radius_range().0;
+            if min <= inner_max {
+                return invalid(format!(
+                    "the {:?} ring ({}) should be bigger than the Inner ring ({})",
+                    ring.role, min, inner_max
+                ));
+            }
+        }
+        let mut ranges: Vec<(f32, f32)> = self.rings.iter().map(Ring::radius_range).collect();
+        ranges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        for pair in ranges.windows(2) {
+            if pair[0].1 >= pair[1].0 {
+                return invalid(format!(
+                    "the rings ({} and {}) should not overlap",
+                    pair[0].1, pair[1].0
+                ));
+            }
+        }
+        self.motion
+            .validate()
+            .map_err(|msg| LevelError::Invalid(format!("motion: {}", msg)))?;
+        self.spin
+            .validate()
+            .map_err(|msg| LevelError::Invalid(format!("spin: {}", msg)))?;
+        // between the rings during the whole round, when they turn and pulse
+        let (shrink, grow) = self.motion.scale_range();
+        let turning = self.motion.angular_speed != 0.0;
+        let between_rings = |point: Vec2, margin: f32| {
+            let (inner_radius, outer_radius) = if turning {
+                (inner.radius_range().1, outer.radius_range().0)
+            } else {
+                (inner.radius_at(point), outer.radius_at(point))
+            };
+            point.length() - margin > inner_radius * grow
+                && point.length() + margin < outer_radius * shrink
+        };
+        if self.spawns.is_empty() {
+            return invalid("no spawn".to_string());
+        }
+        for (index, spawn) in self.spawns.iter().enumerate() {
+            if !between_rings(spawn.position, 0.0) {
+                return invalid(format!("spawn #{} is not between the rings", index));
+            }
+            if !positive(spawn.direction.length()) {
+                return invalid(format!("direction of the spawn #{} is zero", index));
+            }
+        }
+        for (index, obstacle) in self.obstacles.iter().enumerate() {
+            match obstacle.shape {
+                Shape::Bumper { center, radius } => {
+                    if !positive(radius) {
+                        return invalid(format!("radius of the obstacle #{} should be > 0", index));
+                    }
+                    if !between_rings(center, radius) {
+                        return invalid(format!("obstacle #{} is not between the rings", index));
+                    }
+                }
+                Shape::Deflector { start, end } => {
+                    if start == end {
+                        return invalid(format!("deflector #{} has no length", index));
+                    }
+                    if !between_rings(start, 0.0) || !between_rings(end, 0.0) {
+                        return invalid(format!("obstacle #{} is not between the rings", index));
+                    }
+                }
+            }
+        }
+        for (index, teleporter) in self.teleporters.iter().enumerate() {
+            for pad in teleporter.pads.iter() {
+                if !positive(pad.radius) || !between_rings(pad.center, pad.radius) {
+                    return invalid(format!(
+                        "a pad of the teleporter #{} is not between the rings",
+                        index
+                    ));
+                }
+            }
+        }
+        if self.win.versus_points == 0 {
+            return invalid("win.versus_points should be > 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Inputs;
+
+    #[test]
+    fn test_default_level_file() {
+        let level = Level::from_ron(include_str!("../assets/levels/default.level.ron")).unwrap();
+        let default = Level::default();
+        assert_eq!(level.rings, default.rings);
+        assert_eq!(level.spawns, default.spawns);
+        assert_eq!(level.win, default.win);
+        // the positions are rounded in the file
+        assert_eq!(level.obstacles.len(), default.obstacles.len());
+        for (obstacle, expected) in level.obstacles.iter().zip(default.obstacles.iter()) {
+            assert_eq!(obstacle.motion, expected.motion);
+            assert_eq!(obstacle.points, expected.points);
+        }
+        for (teleporter, expected) in level.teleporters.iter().zip(default.teleporters.iter()) {
+            for (pad, expected) in teleporter.pads.iter().zip(expected.pads.iter()) {
+                assert!((pad.center - expected.center).length() < 1e-3);
+            }
+        }
+        // and back
+        assert_eq!(
+            Level::from_ron(&default.to_ron().unwrap()).unwrap(),
+            default
+        );
+    }
+
+    #[test]
+    fn test_apply_and_record() {
+        let mut sim = Simulation::new();
+        Level::default().apply(&mut sim, Difficulty::Hard);
+        assert_eq!(sim.paddles[0].role, PaddleRole::Outer);
+        assert_eq!(sim.paddles[0].radius_origin, RADIUS_EXTERN);
+        assert_eq!(sim.speed_curve, Difficulty::Hard.speed_curve());
+        let level = Level::of(&sim);
+        assert_eq!(level.rings, Level::default().rings);
+        assert_eq!(level.speed_curve, Some(Difficulty::Hard.speed_curve()));
+    }
+
+    #[test]
+    fn test_level_with_tracks() {
+        let level = Level::from_ron(include_str!("../assets/levels/tracks.level.ron")).unwrap();
+        let mut sim = Simulation::new();
+        level.apply(&mut sim, Difficulty::default());
+        assert!(sim.paddles.iter().all(|paddle| paddle.track.is_some()));
+        assert_eq!(Level::of(&sim).rings, level.rings);
+        let (inner, outer) = sim.ring_bounds();
+        assert!((inner - 130.0).abs() < 1e-3 && (outer - 260.0).abs() < 1e-3);
+        // the inner ellipse goes beyond the outer ring
+        let mut overlapping = level.clone();
+        overlapping.rings[1].track = Some(TrackShape::Ellipse {
+            radius_x: 300.0,
+            radius_y: 80.0,
+        });
+        assert!(overlapping.validate().is_err());
+        let mut reversed = level;
+        reversed.rings[0].track = Some(TrackShape::Spline {
+            points: vec![
+                Vec2::new(250.0, 0.0),
+                Vec2::new(0.0, -250.0),
+                Vec2::new(-250.0, 0.0),
+            ],
+        });
+        assert_eq!(
+            reversed.validate().unwrap_err().to_string(),
+            "invalid level: track of the Outer ring: the track should go counterclockwise around the center, without going back"
+        );
+    }
+
+    #[test]
+    fn test_level_with_concentric_rings() {
+        let level = Level::from_ron(include_str!("../assets/levels/rings.level.ron")).unwrap();
+        let mut sim = Simulation::new();
+        level.apply(&mut sim, Difficulty::default());
+        assert_eq!(sim.paddles.len(), 4);
+        assert_eq!(sim.paddles[1].role, PaddleRole::Inner);
+        assert_eq!((sim.paddles[1].offset, sim.paddles[1].points), (PI, 2));
+        assert_eq!(sim.ring_bounds(), (95.0, 290.0));
+        assert_eq!(Level::of(&sim).rings, level.rings);
+        // the outermost ring is an Outer ring
+        let mut swapped = level.clone();
+        swapped.rings[0].role = PaddleRole::Inner;
+        swapped.rings[1].role = PaddleRole::Outer;
+        assert_eq!(
+            swapped.validate().unwrap_err().to_string(),
+            "invalid level: the Inner ring (290) should be smaller than the Outer ring (225)"
+        );
+        let mut overlapping = level;
+        overlapping.rings[2].radius = 225.0;
+        assert_eq!(
+            overlapping.validate().unwrap_err().to_string(),
+            "invalid level: the rings (225 and 225) should not overlap"
+        );
+    }
+
+    #[test]
+    fn test_apply_a_level_with_fewer_rings() {
+        let level = Level::from_ron(include_str!("../assets/levels/rings.level.ron")).unwrap();
+        let mut sim = Simulation::new();
+        level.apply(&mut sim, Difficulty::default());
+        sim.start();
+        let inputs = crate::control::ControlMode::default().dispatch(Some(1.0), None);
+        for _ in 0..60 {
+            sim.step(1.0 / 120.0, &inputs);
+        }
+        // a ring removed in the editor, between two rounds
+        let mut fewer = level;
+        fewer.rings.remove(2);
+        assert!(fewer.validate().is_ok());
+        fewer.apply(&mut sim, Difficulty::default());
+        assert_eq!(sim.paddles.len(), 3);
+        assert!(sim.paddles.get(3).is_none());
+        assert_eq!(Level::of(&sim).rings, fewer.rings);
+        sim.start();
+        for _ in 0..120 {
+            sim.step(1.0 / 120.0, &inputs);
+        }
+    }
+
+    #[test]
+    fn test_level_with_motion() {
+        let level = Level::from_ron(include_str!("../assets/levels/pulse.level.ron")).unwrap();
+        let mut sim = Simulation::new();
+        level.apply(&mut sim, Difficulty::default());
+        assert_eq!(sim.arena, level.motion);
+        for _ in 0..120 {
+            sim.step(1.0 / 120.0, &Inputs::default());
+        }
+        // recorded at rest
+        assert_eq!(Level::of(&sim).rings, level.rings);
+        assert_eq!(Level::of(&sim).motion, level.motion);
+        // a bumper that would touch the outer ring when the rings shrink
+        let mut touching = level;
+        touching.obstacles[0].shape = Shape::Bumper {
+            center: Vec2::new(235.0, 0.0),
+            radius: 12.0,
+        };
+        assert_eq!(
+            touching.validate().unwrap_err().to_string(),
+            "invalid level: obstacle #0 is not between the rings"
+        );
+    }
+
+    #[test]
+    fn test_invalid_levels() {
+        let error = |text: &str| Level::from_ron(text).unwrap_err().to_string();
+        assert!(error("Level(rings: [").starts_with("failed to parse the level"));
+        assert_eq!(
+            error("Level(rings: [(role: Outer, radius: 285.0, paddle_height: 12.0, paddle_arc: 0.5)], spawns: [])"),
+            "invalid level: no Inner ring"
+        );
+        assert_eq!(
+            error(
+                "Level(
+                    rings: [
+                        (role: Outer, radius: 100.0, paddle_height: 12.0, paddle_arc: 0.5),
+                        (role: Inner, radius: 200.0, paddle_height: 4.0, paddle_arc: 0.5),
+                    ],
+                    spawns: [],
+                )"
+            ),
+            "invalid level: the Inner ring (200) should be smaller than the Outer ring (100)"
+        );
+        assert_eq!(
+            error(
+                "Level(
+                    rings: [
+                        (role: Outer, radius: 285.0, paddle_height: 12.0, paddle_arc: 0.5),
+                        (role: Inner, radius: 108.0, paddle_height: 4.0, paddle_arc: 0.5),
+                    ],
+                    spawns: [(position: (0.0, 0.0), direction: (1.0, 0.0))],
+                )"
+            ),
+            "invalid level: spawn #0 is not between the rings"
+        );
+    }
+}
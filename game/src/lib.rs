@@ -0,0 +1,63 @@
+//! Game logic of Keep Inside, without any dependency on bevy.
+//!
+//! The `game` binary drives a [`Simulation`] from bevy systems, but the simulation
+//! can also be stepped headless (tests, scripted rallies, ...).
+
+mod ai;
+mod angular_interval;
+mod arena;
+mod ball;
+mod bot;
+mod control;
+mod controller;
+mod editor;
+mod env;
+mod game_state;
+mod headless;
+mod high_scores;
+mod level;
+mod multiball;
+mod obstacle;
+mod paddle;
+mod physics;
+mod play_mode;
+mod power_up;
+mod replay;
+mod rng;
+mod simulation;
+mod speed_curve;
+mod spin;
+pub mod storage;
+mod teleporter;
+mod timestep;
+mod track;
+
+pub use ai::{find_ring_crossing, AiController};
+pub use angular_interval::{angle_delta, angle_distance, AngularInterval};
+pub use arena::{ArenaMotion, Pulse};
+pub use ball::{Ball, BallId};
+pub use bot::Bot;
+pub use control::{ControlMode, DEFAULT_LINKED_OFFSET};
+pub use controller::{controlled_inputs, PaddleController};
+pub use editor::{LevelEditor, Target, Tool, PICK_DISTANCE};
+pub use env::{BallObservation, Env, EnvRequest, EnvResponse, Observation, PaddleObservation};
+pub use game_state::{GameState, GameStateEvent, COUNTDOWN_SECONDS};
+pub use headless::{run_headless, RunReport};
+pub use high_scores::{format_date, HighScore, HighScores, HIGH_SCORES_LEN};
+pub use level::{Level, LevelError, Ring, Spawn, WinConditions};
+pub use multiball::{collide_balls, Multiball};
+pub use obstacle::{default_obstacles, Motion, Obstacle, Shape, DEFLECTOR_HALF_WIDTH};
+pub use paddle::{Paddle, PaddleRole};
+pub use physics::{
+    find_ball_paddle_collision_point, positive_angle, reflect_2d, reflect_on_moving, Collision,
+};
+pub use play_mode::{PlayMode, VersusScore, VERSUS_POINTS};
+pub use power_up::{Effect, PowerUpKind, PowerUps, Zone, ZoneId};
+pub use replay::{Replay, ReplayConfig, ReplayError, ReplayPlayer, REPLAY_VERSION};
+pub use rng::Rng;
+pub use simulation::{Escape, Event, Inputs, Scoreboard, Simulation, RADIUS_EXTERN, RADIUS_INTERN};
+pub use speed_curve::{Difficulty, SpeedCurve};
+pub use spin::Spin;
+pub use teleporter::{default_teleporters, Exit, Pad, Teleporter};
+pub use timestep::FixedTimestep;
+pub use track::{Track, TrackShape};
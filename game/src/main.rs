@@ -2,22 +2,50 @@
 #![windows_subsystem = "windows"]
 
 use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
     // diagnostic::{FrameTimeDiagnosticsPlugin, PrintDiagnosticsPlugin},
     prelude::*,
+    reflect::TypeUuid,
     render::camera::Camera,
+    utils::BoxedFuture,
     window::CursorMoved,
     window::WindowMode,
 };
 use bevy_easings::*;
 use bevy_prototype_lyon::prelude::*;
-use std::collections::HashSet;
-use std::f32::consts::{FRAC_PI_6, PI};
+use ld47_keep_inside::{
+    controlled_inputs,
+    storage::{self, HighScoresStorage},
+    AiController, Ball, BallId, ControlMode, Difficulty, Event, FixedTimestep, GameState,
+    GameStateEvent, HighScore, HighScores, Inputs, Level, LevelEditor, Pad, Paddle,
+    PaddleController, PaddleRole, PlayMode, PowerUpKind, Replay, ReplayPlayer, Shape, Simulation,
+    Tool, Track, ZoneId, DEFAULT_LINKED_OFFSET, DEFLECTOR_HALF_WIDTH,
+};
+use std::collections::{HashMap, HashSet};
+use std::io::BufReader;
+use std::path::PathBuf;
 //use bevy_input::gamepad::{GamepadEvent, GamepadEventType};
 #[cfg(target_arch = "wasm32")]
 use bevy_webgl2;
 
 #[bevy_main]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let playback = match arg_value("--replay") {
+        Some(path) => {
+            let replay = Replay::read(BufReader::new(std::fs::File::open(path)?))?;
+            Playback(Some(ReplayPlayer::new(replay)))
+        }
+        None => Playback::default(),
+    };
+    let linked_offset = match arg_value("--linked-offset") {
+        Some(degrees) => LinkedOffset(degrees.parse::<f32>()?.to_radians()),
+        None => LinkedOffset::default(),
+    };
+    let high_scores_store = HighScoresStore::load();
+    let mut sim = Simulation::new();
+    sim.scoreboard.best = high_scores_store.high_scores.best();
+    sim.power_ups.enabled = true;
+    Level::default().apply(&mut sim, Difficulty::default());
     let mut app = App::build();
     app.add_resource(Msaa { samples: 4 })
         .add_resource(WindowDescriptor {
@@ -32,24 +60,57 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         })
         .add_plugins(DefaultPlugins)
         .add_plugin(EasingsPlugin)
+        .add_asset::<LevelAsset>()
+        .init_asset_loader::<LevelLoader>()
         .add_event::<GameStateEvent>()
         .init_resource::<GamepadState>()
-        .add_resource(Scoreboard { score: 0, best: 0 })
+        .init_resource::<GameState>()
+        .add_resource(sim)
+        .add_resource(high_scores_store)
+        .add_resource(playback)
+        .add_resource(linked_offset)
+        .init_resource::<Recording>()
+        .init_resource::<DeviceControllers>()
+        .init_resource::<FixedTimestep>()
+        .init_resource::<Difficulty>()
+        .init_resource::<ControlMode>()
+        .init_resource::<PlayMode>()
+        .init_resource::<MultiballMode>()
+        .init_resource::<Bindings>()
+        .init_resource::<DebugOverlay>()
+        .init_resource::<LevelEditing>()
         .add_startup_system(setup.system())
         .add_startup_system(setup_ui.system())
         .add_startup_system(gamepad_connection_system.system())
         .add_system(bevy::input::system::exit_on_esc_system.system())
         .add_system(paddle_control_by_mouse_system.system())
         .add_system(paddle_control_by_gamepad_system.system())
+        .add_system(paddle_control_by_keyboard_system.system())
+        .add_system(control_mode_system.system())
+        .add_system(bindings_system.system())
+        .add_system(level_system.system())
+        .add_system(editor_system.system())
+        .add_system(editor_button_system.system())
+        .add_system(editor_marks_system.system())
         .add_system(ball_movement_system.system())
+        .add_system(sync_ball_entities.system())
+        .add_system(sync_zone_entities.system())
+        .add_system(update_ball_transform.system())
         .add_system(update_paddle_transform.system())
+        .add_system(update_track_paddle_mesh.system())
+        .add_system(update_obstacle_transform.system())
+        .add_system(update_ring_transform.system())
+        .add_system(update_pad_transform.system())
         .add_system(hit_to_fx.system())
         .add_system(update_paddle_fx.system())
         .add_system(custom_ease_system::<ImpactFx>.system())
-        .add_system(start_system.system())
-        .add_system(start_control_system.system())
-        .add_system(hit_as_score.system())
-        .add_system(scoreboard_system.system());
+        .add_system(game_state_system.system())
+        .add_system(game_state_control_system.system())
+        .add_system(overlay_system.system())
+        .add_system(countdown_text_system.system())
+        .add_system(scoreboard_system.system())
+        .add_system(effects_text_system.system())
+        .add_system(debug_overlay_system.system());
     #[cfg(target_arch = "wasm32")]
     app.add_plugin(bevy_webgl2::WebGL2Plugin);
 
@@ -75,33 +136,347 @@ struct GamepadState {
     gamepads: HashSet<Gamepad>,
 }
 
-const RADIUS_EXTERN: f32 = 285.0;
-const RADIUS_INTERN: f32 = 108.0;
+/// An input device able to drive a paddle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Device {
+    Mouse,
+    Keyboard,
+    LeftStick(Gamepad),
+    RightStick(Gamepad),
+}
 
-struct Paddle {
-    radius_origin: f32,
-    angle_origin: f32,
-    angle_speed: f32,
-    half_surface_angle: f32,
-    half_height: f32,
+#[derive(Debug, Clone)]
+struct Binding {
+    player: usize,
+    device: Device,
+    role: PaddleRole,
 }
 
-impl Paddle {
-    fn set_angle(&mut self, angle: f32) {
-        let previous_angle = self.angle_origin;
-        let new_angle = positive_angle(angle);
-        let angle_delta = new_angle - previous_angle;
-        self.angle_origin = new_angle;
-        self.angle_speed = if angle_delta > PI {
-            new_angle - (previous_angle + 2.0 * PI)
-        } else if angle_delta < -PI {
-            new_angle + 2.0 * PI - previous_angle
+/// Which device drives which paddle (for which player)
+#[derive(Debug, Default)]
+struct Bindings(Vec<Binding>);
+
+impl Bindings {
+    fn new(play_mode: PlayMode, gamepads: &[Gamepad]) -> Self {
+        let bind = |player, device, role| Binding {
+            player,
+            device,
+            role,
+        };
+        let mut bindings = vec![];
+        if play_mode.players() == 1 {
+            bindings.push(bind(0, Device::Mouse, PaddleRole::Outer));
+            bindings.push(bind(0, Device::Keyboard, PaddleRole::Inner));
+            for gamepad in gamepads.iter().cloned() {
+                bindings.push(bind(0, Device::LeftStick(gamepad), PaddleRole::Outer));
+                bindings.push(bind(0, Device::RightStick(gamepad), PaddleRole::Inner));
+            }
         } else {
-            angle_delta
+            // player 1 on the outer ring with the mouse (+ the first gamepad if each player has one),
+            // player 2 on the inner ring with the keyboard (+ the last gamepad)
+            bindings.push(bind(0, Device::Mouse, PaddleRole::Outer));
+            bindings.push(bind(1, Device::Keyboard, PaddleRole::Inner));
+            if gamepads.len() >= 2 {
+                bindings.push(bind(0, Device::LeftStick(gamepads[0]), PaddleRole::Outer));
+            }
+            if let Some(gamepad) = gamepads.last() {
+                bindings.push(bind(1, Device::LeftStick(*gamepad), PaddleRole::Inner));
+            }
+        }
+        Bindings(bindings)
+    }
+}
+
+/// The value of `name` in the arguments: `game --replay <file> --level <file>`
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+    }
+    None
+}
+
+/// The default level, used until the level file is loaded (and if it's invalid)
+const DEFAULT_LEVEL_PATH: &str = "levels/default.level.ron";
+
+#[derive(TypeUuid)]
+#[uuid = "6b1c3e0e-2a43-4f0a-9d0b-8f3f4b8a5d21"]
+struct LevelAsset(Level);
+
+#[derive(Default)]
+struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let text = std::str::from_utf8(bytes)?;
+            let level = Level::from_ron(text)
+                .map_err(|err| anyhow::anyhow!("{}: {}", load_context.path().display(), err))?;
+            load_context.set_default_asset(LoadedAsset::new(LevelAsset(level)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        // the asset server only looks at the last extension (`*.level.ron`)
+        &["ron"]
+    }
+}
+
+/// The level played, `changed` until its geometry is applied to the `Simulation` and to the entities
+/// (or until the entities match the level of the replay)
+struct CurrentLevel {
+    /// relative to the assets directory
+    path: String,
+    handle: Handle<LevelAsset>,
+    level: Level,
+    changed: bool,
+}
+
+/// Entities of the geometry of the level (paddles, rings, obstacles, teleporters)
+struct ArenaPart;
+
+/// The editor of the current level, while the `GameState` is `Editing`
+#[derive(Default)]
+struct LevelEditing {
+    editor: Option<LevelEditor>,
+    /// the marks of the editor are outdated
+    redraw: bool,
+    /// result of the last save or play request
+    status: String,
+}
+
+/// Entities drawn by the editor over the arena (spawns, selection, first pad of a new teleporter)
+struct EditorMark;
+
+#[derive(Clone, Copy)]
+enum EditorButton {
+    /// "play from here"
+    Play,
+    Save,
+}
+
+struct EditorText {}
+
+/// The replay of the round in progress
+#[derive(Default)]
+struct Recording(Option<Replay>);
+
+/// Set by `--replay <file>`: the rounds replay the file instead of reading the devices
+#[derive(Default)]
+struct Playback(Option<ReplayPlayer>);
+
+/// Offset (radian) of the inner paddle in the second linked `ControlMode`, set with
+/// `--linked-offset <degrees>`
+struct LinkedOffset(f32);
+
+impl Default for LinkedOffset {
+    fn default() -> Self {
+        LinkedOffset(DEFAULT_LINKED_OFFSET)
+    }
+}
+
+/// Extra balls (see `Multiball`), applied at the next start
+#[derive(Default)]
+struct MultiballMode(bool);
+
+/// The `HighScores` and where to save them
+struct HighScoresStore {
+    storage: Option<Box<dyn HighScoresStorage + Send + Sync>>,
+    high_scores: HighScores,
+    /// rank of the last round in `high_scores`
+    last_rank: Option<usize>,
+}
+
+impl HighScoresStore {
+    /// Without storage (or with unreadable high scores), the game starts with an empty table.
+    fn load() -> Self {
+        let (storage, high_scores) = match storage::default_storage() {
+            Ok(storage) => {
+                let high_scores = storage.load().unwrap_or_else(|err| {
+                    warn!("{}", err);
+                    HighScores::default()
+                });
+                (
+                    Some(Box::new(storage) as Box<dyn HighScoresStorage + Send + Sync>),
+                    high_scores,
+                )
+            }
+            Err(err) => {
+                warn!("{}", err);
+                (None, HighScores::default())
+            }
         };
+        HighScoresStore {
+            storage,
+            high_scores,
+            last_rank: None,
+        }
     }
+
+    /// Add the round that just ended (if good enough) and save the table.
+    fn record(&mut self, sim: &Simulation) {
+        // in versus, the score is only the length of the rallies
+        self.last_rank = None;
+        if sim.play_mode == PlayMode::Versus || sim.scoreboard.score == 0 {
+            return;
+        }
+        self.last_rank = self.high_scores.insert(HighScore {
+            score: sim.scoreboard.score,
+            date: storage::now_unix_seconds(),
+            mode: sim.play_mode,
+            seed: sim.seed,
+        });
+        if let (Some(_), Some(storage)) = (self.last_rank, self.storage.as_ref()) {
+            if let Err(err) = storage.save(&self.high_scores) {
+                warn!("{}", err);
+            }
+        }
+    }
+}
+
+/// Last cursor position (in the world) since the previous step, and a click to release the balls
+#[derive(Default)]
+struct MouseController {
+    cursor: Option<Vec2>,
+    release: bool,
 }
 
+impl PaddleController for MouseController {
+    fn target_angle(&mut self, _paddle: &Paddle, _balls: &[Ball], _tick: u64) -> Option<f32> {
+        self.cursor.take().map(|cursor| cursor.y.atan2(cursor.x))
+    }
+
+    fn release(&mut self, _paddle: &Paddle, _tick: u64) -> bool {
+        std::mem::take(&mut self.release)
+    }
+}
+
+/// Last stick position (out of the dead zone) since the previous step, and the button (A) to release the balls
+#[derive(Default)]
+struct StickController {
+    stick: Option<Vec2>,
+    release: bool,
+}
+
+impl PaddleController for StickController {
+    fn target_angle(&mut self, _paddle: &Paddle, _balls: &[Ball], _tick: u64) -> Option<f32> {
+        self.stick.take().map(|stick| stick.y.atan2(stick.x))
+    }
+
+    fn release(&mut self, _paddle: &Paddle, _tick: u64) -> bool {
+        std::mem::take(&mut self.release)
+    }
+}
+
+/// rotation speed (radian per second) of a paddle with the keyboard
+const KEYBOARD_ANGLE_SPEED: f32 = 1.5 * std::f32::consts::PI;
+
+/// Rotation (radian) requested with the keys since the previous step, and Space to release the balls
+#[derive(Default)]
+struct KeyboardController {
+    rotation: f32,
+    release: bool,
+}
+
+impl PaddleController for KeyboardController {
+    fn target_angle(&mut self, paddle: &Paddle, _balls: &[Ball], _tick: u64) -> Option<f32> {
+        let rotation = std::mem::take(&mut self.rotation);
+        if rotation != 0.0 {
+            Some(paddle.angle_origin + rotation)
+        } else {
+            None
+        }
+    }
+
+    fn release(&mut self, _paddle: &Paddle, _tick: u64) -> bool {
+        std::mem::take(&mut self.release)
+    }
+}
+
+/// The `PaddleController` of every device, what they requested is consumed by the next step
+#[derive(Default)]
+struct DeviceControllers {
+    mouse: MouseController,
+    keyboard: KeyboardController,
+    sticks: HashMap<Device, StickController>,
+    /// auto-paddle: the AI drives every paddle instead of the devices
+    ai: Option<AiController>,
+}
+
+impl DeviceControllers {
+    /// What the devices bound to each role requested (with the paddle that drives the role), the
+    /// first device with a request wins.
+    ///
+    /// With one player, a release from any device releases the balls of every paddle.
+    fn requested(&mut self, bindings: &Bindings, sim: &Simulation) -> Inputs {
+        if let Some(ai) = self.ai.as_mut() {
+            return controlled_inputs(ai, sim);
+        }
+        let mut inputs = Inputs::default();
+        for paddle in sim.driving_paddles() {
+            for binding in bindings
+                .0
+                .iter()
+                .filter(|binding| binding.role == paddle.role)
+            {
+                let controller: &mut dyn PaddleController = match binding.device {
+                    Device::Mouse => &mut self.mouse,
+                    Device::Keyboard => &mut self.keyboard,
+                    device => self.sticks.entry(device).or_default(),
+                };
+                // every controller is consumed, even if an other one already requested an angle
+                if let Some(angle) = controller.target_angle(paddle, &sim.balls, sim.tick) {
+                    if inputs.angle_for(paddle.role).is_none() {
+                        inputs.set_angle(paddle.role, angle - paddle.offset);
+                    }
+                }
+                if controller.release(paddle, sim.tick) {
+                    inputs.set_release(paddle.role);
+                }
+            }
+        }
+        if sim.play_mode.players() == 1 && (inputs.outer_release || inputs.inner_release) {
+            inputs.outer_release = true;
+            inputs.inner_release = true;
+        }
+        inputs
+    }
+}
+
+/// Link an entity to the paddle (index) in the `Simulation`
+struct PaddleRef(usize);
+
+/// A paddle on a track: its mesh is rebuilt when it moves, `drawn` is the angle of the paddle, the
+/// rotation and the scale of the arena (see `ArenaMotion`) of the mesh
+struct TrackPaddle {
+    drawn: Option<(f32, f32, f32)>,
+}
+
+/// Link the drawing of a ring (at rest) to the paddle (index) of the ring in the `Simulation`
+struct RingRef(usize);
+
+/// Link an entity to the pad of a teleporter in the `Simulation`
+struct PadRef {
+    teleporter: usize,
+    pad: usize,
+}
+
+/// Link an entity to the ball in the `Simulation`
+struct BallRef(BallId);
+
+/// Link an entity to the obstacle (index) in the `Simulation`
+struct ObstacleRef(usize);
+
+/// Link an entity to the power-up zone in the `Simulation`
+struct ZoneRef(ZoneId);
+
 struct Hit {
     direction: Vec3,
 }
@@ -120,36 +495,60 @@ impl Lerp for ImpactFx {
     }
 }
 
-struct Ball {
-    mvt_dir: Vec3,
-    velocity_indicator: i32,
-    radius: f32,
-}
+struct ScoreText {}
+struct ScoreBestText {}
+struct DebugText {}
+/// The active effects of the power-ups, with their remaining time
+struct EffectsText {}
+/// Root of the UI specific to the current `GameState`
+struct Overlay {}
+struct CountdownText {}
 
-impl Ball {
-    //TODO try a quadratic, log or bezier curve
-    fn velocity(&self) -> f32 {
-        410.0 + 10.0 * self.velocity_indicator as f32
-    }
+/// Handles shared by the UI builders
+struct UiAssets {
+    font_score: Handle<Font>,
+    font_text: Handle<Font>,
+    transparent: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
 }
 
-struct Scoreboard {
-    score: usize,
-    best: usize,
+/// Toggled by F3, to inspect the internal state of the game (speed curve, ...)
+#[derive(Default)]
+struct DebugOverlay {
+    visible: bool,
 }
-struct ScoreText {}
-struct ScoreBestText {}
-enum GameStateEvent {
-    Start,
+const RESTART_MESSAGE: &str = "Click or Button (A) to restart";
+
+fn game_over_message(sim: &Simulation) -> String {
+    if sim.scoreboard.won {
+        return format!("You win!\n{}", RESTART_MESSAGE);
+    }
+    match sim.scoreboard.versus.winner(sim.win.versus_points) {
+        Some(PaddleRole::Outer) if sim.play_mode == PlayMode::Versus => {
+            format!("Player 1 (outer ring) wins\n{}", RESTART_MESSAGE)
+        }
+        Some(PaddleRole::Inner) if sim.play_mode == PlayMode::Versus => {
+            format!("Player 2 (inner ring) wins\n{}", RESTART_MESSAGE)
+        }
+        _ => format!("Game Over\n{}", RESTART_MESSAGE),
+    }
 }
 
 fn setup_ui(
     commands: &mut Commands,
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    high_scores_store: Res<HighScoresStore>,
 ) {
     let font_score_handle = asset_server.load("Eduardo-Barrasa.ttf");
     let font_text_handle = asset_server.load("FiraMono-Medium.ttf");
+    let transparent = materials.add(Color::NONE.into());
+    commands.insert_resource(UiAssets {
+        font_score: font_score_handle.clone(),
+        font_text: font_text_handle.clone(),
+        transparent: transparent.clone(),
+        button: materials.add(Color::rgb(0.2, 0.2, 0.8).into()),
+    });
     commands
         .spawn(CameraUiBundle::default())
         // scoreboard
@@ -161,7 +560,7 @@ fn setup_ui(
                 flex_direction: FlexDirection::ColumnReverse,
                 ..Default::default()
             },
-            material: materials.add(Color::NONE.into()),
+            material: transparent,
             draw: Draw {
                 // is_transparent: true,
                 ..Default::default()
@@ -190,7 +589,7 @@ fn setup_ui(
         .spawn(TextBundle {
             text: Text {
                 font: font_text_handle.clone(),
-                value: "Click or Button (A) on Gamepad\nto spawn a ball and to start".to_string(),
+                value: "P or Button (Start) to pause\nF2 to link / unlink the paddles\nF4 for solo / co-op / versus\nF5 for auto-paddle (AI)\nF6 for multiball\nF7 for easy / normal / hard\nE to edit the level\nClick, Space or Button (A) to release a caught ball".to_string(),
                 style: TextStyle {
                     color: Color::rgb(0.2, 0.2, 0.8),
                     font_size: 20.0,
@@ -210,8 +609,8 @@ fn setup_ui(
         })
         .spawn(TextBundle {
             text: Text {
-                font: font_text_handle,
-                value: "Best: 0".to_string(),
+                font: font_text_handle.clone(),
+                value: format!("Best: {}", high_scores_store.high_scores.best()),
                 style: TextStyle {
                     color: Color::rgb(0.2, 0.2, 0.8),
                     font_size: 20.0,
@@ -229,7 +628,271 @@ fn setup_ui(
             },
             ..Default::default()
         })
-        .with(ScoreBestText {});
+        .with(ScoreBestText {})
+        .spawn(TextBundle {
+            text: Text {
+                font: font_text_handle.clone(),
+                value: "".to_string(),
+                style: TextStyle {
+                    color: Color::rgb(0.4, 0.4, 0.4),
+                    font_size: 14.0,
+                    alignment: TextAlignment::default(),
+                },
+            },
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with(DebugText {})
+        .spawn(TextBundle {
+            text: Text {
+                font: font_text_handle,
+                value: "".to_string(),
+                style: TextStyle {
+                    color: Color::rgb(0.2, 0.2, 0.8),
+                    font_size: 20.0,
+                    alignment: TextAlignment::default(),
+                },
+            },
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    right: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with(EffectsText {});
+}
+
+/// Root node of an overlay: centered on the top half of the window
+fn spawn_overlay<'a>(commands: &'a mut Commands, ui: &UiAssets) -> &'a mut Commands {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(50.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    ..Default::default()
+                },
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: ui.transparent.clone(),
+            ..Default::default()
+        })
+        .with(Overlay {})
+}
+
+fn overlay_text(font: Handle<Font>, value: String, font_size: f32, color: Color) -> TextBundle {
+    TextBundle {
+        text: Text {
+            value,
+            font,
+            style: TextStyle {
+                font_size,
+                color,
+                alignment: TextAlignment::default(),
+            },
+        },
+        ..Default::default()
+    }
+}
+
+fn setup_title_ui(commands: &mut Commands, ui: &UiAssets, high_scores: &HighScores) {
+    let table = high_scores
+        .entries
+        .iter()
+        .take(5)
+        .enumerate()
+        .map(|(rank, entry)| format!("#{} {}", rank + 1, entry))
+        .collect::<Vec<_>>()
+        .join("\n");
+    spawn_overlay(commands, ui).with_children(|parent| {
+        parent
+            .spawn(overlay_text(
+                ui.font_score.clone(),
+                "Keep Inside".to_string(),
+                60.0,
+                Color::rgb_u8(0x00, 0xAA, 0xAA),
+            ))
+            .spawn(overlay_text(
+                ui.font_text.clone(),
+                "Click or Button (A) on Gamepad to start".to_string(),
+                20.0,
+                Color::rgb(0.2, 0.2, 0.8),
+            ))
+            .spawn(overlay_text(
+                ui.font_text.clone(),
+                table,
+                16.0,
+                Color::rgb(0.2, 0.2, 0.8),
+            ));
+    });
+}
+
+fn setup_countdown_ui(commands: &mut Commands, ui: &UiAssets) {
+    spawn_overlay(commands, ui).with_children(|parent| {
+        parent
+            .spawn(overlay_text(
+                ui.font_score.clone(),
+                "".to_string(),
+                60.0,
+                Color::rgb(0.8, 0.0, 0.0),
+            ))
+            .with(CountdownText {});
+    });
+}
+
+fn setup_paused_ui(commands: &mut Commands, ui: &UiAssets) {
+    spawn_overlay(commands, ui).with_children(|parent| {
+        parent
+            .spawn(overlay_text(
+                ui.font_text.clone(),
+                "Paused".to_string(),
+                30.0,
+                Color::rgb(0.2, 0.2, 0.8),
+            ))
+            .spawn(overlay_text(
+                ui.font_text.clone(),
+                "P or Button (Start) to resume, Backspace or Button (Select) for the title"
+                    .to_string(),
+                16.0,
+                Color::rgb(0.2, 0.2, 0.8),
+            ));
+    });
+}
+
+fn setup_game_over_ui(
+    commands: &mut Commands,
+    ui: &UiAssets,
+    sim: &Simulation,
+    rank: Option<usize>,
+) {
+    let message = match rank {
+        Some(rank) => format!("New high score: #{}\n{}", rank + 1, game_over_message(sim)),
+        None => game_over_message(sim),
+    };
+    spawn_overlay(commands, ui).with_children(|parent| {
+        parent.spawn(overlay_text(
+            ui.font_text.clone(),
+            message,
+            30.0,
+            Color::rgb(0.8, 0.0, 0.0),
+        ));
+    });
+}
+
+/// A panel on the top right corner (outside of the arena): the tool, the selection and the buttons
+fn setup_editor_ui(commands: &mut Commands, ui: &UiAssets) {
+    let button = |parent: &mut ChildBuilder, label: &str, action: EditorButton| {
+        parent
+            .spawn(ButtonBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(4.0)),
+                    padding: Rect::all(Val::Px(4.0)),
+                    ..Default::default()
+                },
+                material: ui.button.clone(),
+                ..Default::default()
+            })
+            .with(action)
+            .with_children(|parent| {
+                parent.spawn(overlay_text(
+                    ui.font_text.clone(),
+                    label.to_string(),
+                    16.0,
+                    Color::WHITE,
+                ));
+            });
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(30.0),
+                    right: Val::Px(5.0),
+                    ..Default::default()
+                },
+                align_items: AlignItems::FlexEnd,
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: ui.transparent.clone(),
+            ..Default::default()
+        })
+        .with(Overlay {})
+        .with_children(|parent| {
+            parent
+                .spawn(overlay_text(
+                    ui.font_text.clone(),
+                    "".to_string(),
+                    14.0,
+                    Color::rgb(0.2, 0.2, 0.8),
+                ))
+                .with(EditorText {})
+                .spawn(overlay_text(
+                    ui.font_text.clone(),
+                    "Drag to move, 1-5 or Tab for the tools, Delete to remove\nUp / Down to resize, Left / Right to rotate\nS to save, Enter to play, E to leave".to_string(),
+                    14.0,
+                    Color::rgb(0.4, 0.4, 0.4),
+                ));
+            button(parent, "Play from here", EditorButton::Play);
+            button(parent, "Save", EditorButton::Save);
+        });
+}
+
+/// Replace the overlay when the kind of `GameState` changes
+fn overlay_system(
+    commands: &mut Commands,
+    game_state: Res<GameState>,
+    ui: Res<UiAssets>,
+    sim: Res<Simulation>,
+    high_scores_store: Res<HighScoresStore>,
+    mut displayed: Local<Option<GameState>>,
+    query_overlays: Query<(Entity, &Overlay)>,
+) {
+    if displayed.is_some_and(|displayed| displayed.same_kind(&game_state)) {
+        return;
+    }
+    *displayed = Some(*game_state);
+    for (entity, _) in query_overlays.iter() {
+        commands.despawn_recursive(entity);
+    }
+    match *game_state {
+        GameState::Title => setup_title_ui(commands, &ui, &high_scores_store.high_scores),
+        GameState::Countdown { .. } => setup_countdown_ui(commands, &ui),
+        GameState::Playing => (),
+        GameState::Paused => setup_paused_ui(commands, &ui),
+        GameState::GameOver => setup_game_over_ui(commands, &ui, &sim, high_scores_store.last_rank),
+        GameState::Editing => setup_editor_ui(commands, &ui),
+    }
+}
+
+fn countdown_text_system(
+    game_state: Res<GameState>,
+    mut query_countdowntext: Query<(&mut Text, &CountdownText)>,
+) {
+    if let GameState::Countdown { remaining } = *game_state {
+        for (mut text, _) in query_countdowntext.iter_mut() {
+            text.value = format!("{}", remaining.ceil());
+        }
+    }
 }
 
 fn add_paddle(
@@ -237,6 +900,7 @@ fn add_paddle(
     //asset_server: Res<AssetServer>,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<ColorMaterial>>,
+    index: usize,
     radius: f32,
     height: f32,
     surface_angle: f32,
@@ -260,49 +924,220 @@ fn add_paddle(
             paddle_material,
             meshes,
             Vec3::new(0.0, 0.0, 0.0),
-            &StrokeOptions::default().with_line_width(height), //.with_line_cap(LineCap::Round)
-                                                               //.with_line_join(LineJoin::Round)
+            &paddle_stroke_options(height),
         ))
-        .with(Paddle {
-            radius_origin: radius,
-            half_surface_angle: surface_angle / 2.0,
-            half_height: height / 2.0,
-            angle_origin: 0.0,
-            angle_speed: 0.0,
-        })
+        .with(PaddleRef(index))
+        .with(ArenaPart)
         .spawn(primitive(
             circle_material,
             meshes,
             ShapeType::Circle(radius),
             TessellationMode::Stroke(&StrokeOptions::default().with_line_width(1.0)),
             Vec3::zero().into(),
-        ));
+        ))
+        .with(RingRef(index))
+        .with(ArenaPart);
+}
+
+fn add_obstacle(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    index: usize,
+    shape: &Shape,
+) {
+    let material = materials.add(Color::rgb(0.3, 0.3, 0.35).into());
+    match shape {
+        Shape::Bumper { center, radius } => {
+            commands.spawn(primitive(
+                material,
+                meshes,
+                ShapeType::Circle(*radius),
+                TessellationMode::Fill(&FillOptions::default()),
+                center.extend(0.0).into(),
+            ));
+        }
+        Shape::Deflector { start, end } => {
+            let mut builder = PathBuilder::new();
+            builder.move_to(point(start.x, start.y));
+            builder.line_to(point(end.x, end.y));
+            commands.spawn(
+                builder.build().stroke(
+                    material,
+                    meshes,
+                    Vec3::new(0.0, 0.0, 0.0),
+                    &StrokeOptions::default()
+                        .with_line_width(DEFLECTOR_HALF_WIDTH * 2.0)
+                        .with_line_cap(LineCap::Round),
+                ),
+            );
+        }
+    }
+    commands.with(ObstacleRef(index)).with(ArenaPart);
+}
+
+/// A pad: a ring with a mark in the direction of the pad
+fn add_teleporter_pad(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    material: Handle<ColorMaterial>,
+    teleporter: usize,
+    index: usize,
+    pad: &Pad,
+) {
+    commands
+        .spawn(primitive(
+            material.clone(),
+            meshes,
+            ShapeType::Circle(pad.radius),
+            TessellationMode::Stroke(&StrokeOptions::default().with_line_width(3.0)),
+            pad.center.extend(0.0).into(),
+        ))
+        .with(PadRef {
+            teleporter,
+            pad: index,
+        })
+        .with(ArenaPart);
+    let mut builder = PathBuilder::new();
+    builder.move_to(point(0.0, 0.0));
+    builder.line_to(point(
+        pad.radius * pad.angle.cos(),
+        pad.radius * pad.angle.sin(),
+    ));
+    commands
+        .spawn(
+            builder.build().stroke(
+                material,
+                meshes,
+                pad.center.extend(0.0),
+                &StrokeOptions::default()
+                    .with_line_width(2.0)
+                    .with_line_cap(LineCap::Round),
+            ),
+        )
+        .with(PadRef {
+            teleporter,
+            pad: index,
+        })
+        .with(ArenaPart);
+}
+
+/// The lyon path through `points`
+fn polyline(points: &[Vec2], close: bool) -> PathBuilder {
+    let mut builder = PathBuilder::new();
+    builder.move_to(point(points[0].x, points[0].y));
+    for p in points[1..].iter() {
+        builder.line_to(point(p.x, p.y));
+    }
+    if close {
+        builder.close();
+    }
+    builder
+}
+
+fn paddle_stroke_options(height: f32) -> StrokeOptions {
+    // round caps, like the end caps of the paddle in the collision
+    StrokeOptions::default()
+        .with_line_width(height)
+        .with_line_cap(LineCap::Round)
+}
+
+/// A paddle on a track (see `TrackPaddle`) and its track
+fn add_track_paddle(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    index: usize,
+    track: &Track,
+    height: f32,
+) {
+    let paddle_material = materials.add(Color::rgb(0.1, 0.4, 0.5).into());
+    let track_material = materials.add(Color::rgba(0.5, 0.4, 0.1, 0.8).into());
+    // the mesh of the paddle is built by `update_track_paddle_mesh`
+    commands
+        .spawn(polyline(&track.points()[..2], false).build().stroke(
+            paddle_material,
+            meshes,
+            Vec3::new(0.0, 0.0, 0.0),
+            &paddle_stroke_options(height),
+        ))
+        .with(PaddleRef(index))
+        .with(TrackPaddle { drawn: None })
+        .with(ArenaPart)
+        // the track at rest, turned and scaled by `update_ring_transform`
+        .spawn(
+            polyline(Track::new(track.shape.clone()).points(), true)
+                .build()
+                .stroke(
+                    track_material,
+                    meshes,
+                    Vec3::new(0.0, 0.0, 0.0),
+                    &StrokeOptions::default().with_line_width(1.0),
+                ),
+        )
+        .with(RingRef(index))
+        .with(ArenaPart);
+}
+
+/// Spawn the entities of the geometry of `sim` (see `ArenaPart`)
+fn spawn_arena(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    sim: &Simulation,
+) {
+    for (index, paddle) in sim.paddles.iter().enumerate() {
+        match paddle.track.as_ref() {
+            Some(track) => add_track_paddle(
+                commands,
+                meshes,
+                materials,
+                index,
+                track,
+                paddle.half_height * 2.0,
+            ),
+            None => add_paddle(
+                commands,
+                meshes,
+                materials,
+                index,
+                paddle.radius_origin,
+                paddle.half_height * 2.0,
+                paddle.half_surface_angle * 2.0,
+            ),
+        }
+    }
+    for (index, teleporter) in sim.teleporters.iter().enumerate() {
+        // the 2 pads of a teleporter have the same color
+        let hue = (index as f32 * 0.3) % 1.0;
+        let material = materials.add(Color::rgb(0.2 + 0.6 * hue, 0.6, 0.8 - 0.6 * hue).into());
+        for (pad_index, pad) in teleporter.pads.iter().enumerate() {
+            add_teleporter_pad(commands, meshes, material.clone(), index, pad_index, pad);
+        }
+    }
+    for (index, obstacle) in sim.obstacles.iter().enumerate() {
+        add_obstacle(commands, meshes, materials, index, &obstacle.shape);
+    }
 }
 
 fn setup(
     commands: &mut Commands,
-    //asset_server: Res<AssetServer>,
+    asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    sim: Res<Simulation>,
 ) {
     let camera = Camera2dBundle::default();
     let camera_e = commands.spawn(camera).current_entity().unwrap();
-    add_paddle(
-        commands,
-        &mut meshes,
-        &mut materials,
-        RADIUS_EXTERN,
-        12.0,
-        FRAC_PI_6,
-    );
-    add_paddle(
-        commands,
-        &mut meshes,
-        &mut materials,
-        RADIUS_INTERN,
-        4.0,
-        FRAC_PI_6,
-    );
+    spawn_arena(commands, &mut meshes, &mut materials, &sim);
+    // relative to the assets directory
+    let level_path = arg_value("--level").unwrap_or_else(|| DEFAULT_LEVEL_PATH.to_string());
+    commands.insert_resource(CurrentLevel {
+        handle: asset_server.load(level_path.as_str()),
+        path: level_path,
+        level: Level::default(),
+        changed: false,
+    });
     commands.insert_resource(State {
         cursor_moved_event_reader: Default::default(),
         camera_e,
@@ -316,56 +1151,433 @@ fn setup(
     )));
 }
 
-fn start_system(
-    commands: &mut Commands,
-    mut scoreboard: ResMut<Scoreboard>,
+fn game_state_system(
+    time: Res<Time>,
+    mut game_state: ResMut<GameState>,
+    mut sim: ResMut<Simulation>,
+    difficulty: Res<Difficulty>,
+    play_mode: Res<PlayMode>,
+    multiball_mode: Res<MultiballMode>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut high_scores_store: ResMut<HighScoresStore>,
+    mut timestep: ResMut<FixedTimestep>,
+    mut recording: ResMut<Recording>,
+    mut playback: ResMut<Playback>,
     mut state: ResMut<State>,
     game_state_events: Res<Events<GameStateEvent>>,
+) {
+    for ev in state.game_state_event_reader.iter(&game_state_events) {
+        if let Some(next) = game_state.on_event(*ev) {
+            match next {
+                GameState::Countdown { .. } => {
+                    recording.0 = None;
+                    if let Some(player) = playback.0.as_mut() {
+                        player.rewind();
+                        let best = sim.scoreboard.best;
+                        *sim = player.replay.simulation();
+                        sim.scoreboard.best = best;
+                        // the arena of the replay
+                        current_level.changed = true;
+                        timestep.step_seconds = player.replay.config.step_seconds;
+                    } else {
+                        current_level.level.apply(&mut sim, *difficulty);
+                        sim.play_mode = *play_mode;
+                        sim.multiball.enabled = multiball_mode.0;
+                        sim.seed = storage::now_unix_seconds();
+                        sim.start();
+                    }
+                }
+                GameState::Editing if playback.0.is_some() => {
+                    warn!("the level of a replay can't be edited");
+                    continue;
+                }
+                // the best score is already committed by the simulation on game over
+                GameState::GameOver if playback.0.is_none() => {
+                    high_scores_store.record(&sim);
+                    if let Some(replay) = recording.0.take() {
+                        match storage::save_replay(&replay) {
+                            Ok(location) => info!("replay saved in {}", location),
+                            Err(err) => warn!("{}", err),
+                        }
+                    }
+                }
+                _ => (),
+            }
+            *game_state = next;
+        }
+    }
+    *game_state = game_state.tick(time.delta_seconds());
+}
+
+/// Take the level when it's loaded (or modified), and apply it between the rounds
+fn level_system(
+    commands: &mut Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    query_balls: Query<(Entity, &Ball)>,
+    mut reader: Local<EventReader<AssetEvent<LevelAsset>>>,
+    level_events: Res<Events<AssetEvent<LevelAsset>>>,
+    levels: Res<Assets<LevelAsset>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut sim: ResMut<Simulation>,
+    difficulty: Res<Difficulty>,
+    game_state: Res<GameState>,
+    playback: Res<Playback>,
+    arena_query: Query<Entity, With<ArenaPart>>,
 ) {
-    let material = materials.add(Color::rgb(0.8, 0.0, 0.0).into());
-    let radius = 5.0;
-    for ev in state.game_state_event_reader.iter(&game_state_events) {
-        match ev {
-            GameStateEvent::Start => {
-                scoreboard.best = scoreboard.best.max(scoreboard.score);
-                scoreboard.score = 0;
-                // remove existing balls
-                for (entity, _) in query_balls.iter() {
-                    commands.despawn(entity);
+    for event in reader.iter(&level_events) {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle }
+                if *handle == current_level.handle =>
+            {
+                if let Some(asset) = levels.get(handle) {
+                    info!("level '{}' loaded", asset.0.name);
+                    current_level.level = asset.0.clone();
+                    current_level.changed = true;
                 }
-                // ball
-                commands
-                    // .spawn(SpriteBundle {
-                    //     material: ball_material.clone(),
-                    //     transform: Transform::from_translation(Vec3::new(
-                    //         10.0,
-                    //         -(RADIUS_EXTERN + RADIUS_INTERN) / 2.0,
-                    //         1.0,
-                    //     )),
-                    //     ..Default::default()
-                    // })
-                    .spawn(primitive(
-                        material.clone(),
-                        &mut meshes,
-                        ShapeType::Circle(radius),
-                        TessellationMode::Fill(&FillOptions::default()),
-                        Vec3::new(10.0, -(RADIUS_EXTERN + RADIUS_INTERN) / 2.0, 1.0).into(),
-                    ))
-                    .with(Ball {
-                        velocity_indicator: 0,
-                        mvt_dir: Vec3::new(0.5, -0.5, 0.0).normalize(),
-                        radius,
-                    });
             }
+            _ => (),
+        }
+    }
+    // not during a round
+    if !current_level.changed || matches!(*game_state, GameState::Playing | GameState::Paused) {
+        return;
+    }
+    current_level.changed = false;
+    // a replay has its own level (see `ReplayConfig`)
+    if playback.0.is_none() {
+        current_level.level.apply(&mut sim, *difficulty);
+    }
+    for entity in arena_query.iter() {
+        commands.despawn(entity);
+    }
+    spawn_arena(commands, &mut meshes, &mut materials, &sim);
+}
+
+/// Edit the current level with the mouse and the keyboard while the `GameState` is `Editing`:
+/// every change is applied to the current level (and to the arena by `level_system`)
+fn editor_system(
+    mut editing: ResMut<LevelEditing>,
+    mut current_level: ResMut<CurrentLevel>,
+    game_state: Res<GameState>,
+    state: Res<State>,
+    mut cursor_moved_event_reader: Local<EventReader<CursorMoved>>,
+    cursor_moved_events: Res<Events<CursorMoved>>,
+    mut cursor: Local<Option<Vec2>>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    wnds: Res<Windows>,
+    q_camera: Query<(&Camera, &Transform)>,
+) {
+    if let Ok((_, camera_transform)) = q_camera.get(state.camera_e) {
+        for ev in cursor_moved_event_reader.iter(&cursor_moved_events) {
+            let pos_wld = find_mouse_position(ev, &wnds, &camera_transform);
+            *cursor = Some(pos_wld.truncate().truncate());
+        }
+    }
+    let editing = &mut *editing;
+    if *game_state != GameState::Editing {
+        if editing.editor.take().is_some() {
+            editing.redraw = true;
+        }
+        return;
+    }
+    if editing.editor.is_none() {
+        editing.status = String::new();
+        editing.redraw = true;
+    }
+    let editor = editing
+        .editor
+        .get_or_insert_with(|| LevelEditor::new(current_level.level.clone()));
+    let mut touched = false;
+    if let Some(cursor) = *cursor {
+        if mouse_button_input.just_pressed(MouseButton::Left) {
+            editor.press(cursor);
+            touched = true;
+        } else if mouse_button_input.pressed(MouseButton::Left) {
+            editor.drag_to(cursor);
+        }
+    }
+    if mouse_button_input.just_released(MouseButton::Left) {
+        editor.release();
+    }
+    for (key, tool) in [
+        (KeyCode::Key1, Tool::Select),
+        (KeyCode::Key2, Tool::Bumper),
+        (KeyCode::Key3, Tool::Teleporter),
+        (KeyCode::Key4, Tool::Spawn),
+        (KeyCode::Key5, Tool::Ring),
+    ]
+    .iter()
+    {
+        if keyboard_input.just_pressed(*key) {
+            editor.tool = *tool;
+            editor.pending_pad = None;
+            touched = true;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        editor.tool = editor.tool.next();
+        editor.pending_pad = None;
+        touched = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        editor.delete_selected();
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        editor.resize_selected(1.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        editor.resize_selected(-1.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        editor.rotate_selected(5f32.to_radians());
+    }
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        editor.rotate_selected(-5f32.to_radians());
+    }
+    if editor.take_changed() {
+        current_level.level = editor.level.clone();
+        current_level.changed = true;
+        touched = true;
+    }
+    editing.redraw |= touched;
+}
+
+/// "Play from here" and "Save", by the buttons of the editor or by the keyboard (Enter, S)
+fn editor_button_system(
+    mut editing: ResMut<LevelEditing>,
+    current_level: Res<CurrentLevel>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut game_state_events: ResMut<Events<GameStateEvent>>,
+    button_query: Query<(&Interaction, &EditorButton), Mutated<Interaction>>,
+) {
+    if editing.editor.is_none() {
+        return;
+    }
+    let mut play = keyboard_input.just_pressed(KeyCode::Return);
+    let mut save = keyboard_input.just_pressed(KeyCode::S);
+    for (interaction, button) in button_query.iter() {
+        if *interaction == Interaction::Clicked {
+            match button {
+                EditorButton::Play => play = true,
+                EditorButton::Save => save = true,
+            }
+        }
+    }
+    if !play && !save {
+        return;
+    }
+    // the level is already applied to the simulation (see `level_system`)
+    let checked = current_level
+        .level
+        .validate()
+        .map_err(|err| err.to_string());
+    let result = match checked {
+        Ok(()) if save => save_level(&current_level.level, &current_level.path),
+        Ok(()) => Ok(String::new()),
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(location) => {
+            if save {
+                info!("level saved in {}", location);
+                editing.status = format!("saved in {}", location);
+            }
+            if play {
+                game_state_events.send(GameStateEvent::Play);
+            }
+        }
+        Err(err) => {
+            warn!("{}", err);
+            editing.status = err;
+        }
+    }
+}
+
+/// The assets directory, like the one of the `AssetServer`
+#[cfg(not(target_arch = "wasm32"))]
+fn assets_dir() -> PathBuf {
+    std::env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+        })
+        .unwrap_or_default()
+        .join("assets")
+}
+
+/// Write the level in its file, return the location
+#[cfg(not(target_arch = "wasm32"))]
+fn save_level(level: &Level, path: &str) -> Result<String, String> {
+    let file = assets_dir().join(path);
+    let text = level.to_ron().map_err(|err| err.to_string())?;
+    std::fs::write(&file, text)
+        .map_err(|err| format!("failed to save the level in {}: {}", file.display(), err))?;
+    Ok(file.display().to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_level(_level: &Level, _path: &str) -> Result<String, String> {
+    Err("the levels can't be saved on the web".to_string())
+}
+
+/// Redraw the marks of the editor when it changes, and update its text
+fn editor_marks_system(
+    commands: &mut Commands,
+    mut editing: ResMut<LevelEditing>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    marks_query: Query<Entity, With<EditorMark>>,
+    mut text_query: Query<&mut Text, With<EditorText>>,
+) {
+    if let Some(editor) = editing.editor.as_ref() {
+        for mut text in text_query.iter_mut() {
+            text.value = format!(
+                "Tool: {}\n{}\n{}",
+                editor.tool,
+                editor.describe_selected(),
+                editing.status
+            );
+        }
+    }
+    if !editing.redraw {
+        return;
+    }
+    editing.redraw = false;
+    for entity in marks_query.iter() {
+        commands.despawn(entity);
+    }
+    let editor = match editing.editor.as_ref() {
+        Some(editor) => editor,
+        None => return,
+    };
+    let material = materials.add(Color::rgb(0.9, 0.5, 0.0).into());
+    // a spawn: a dot and its direction
+    for spawn in editor.level.spawns.iter() {
+        commands
+            .spawn(primitive(
+                material.clone(),
+                &mut meshes,
+                ShapeType::Circle(4.0),
+                TessellationMode::Fill(&FillOptions::default()),
+                spawn.position.extend(2.0).into(),
+            ))
+            .with(EditorMark);
+        let mut builder = PathBuilder::new();
+        builder.move_to(point(0.0, 0.0));
+        builder.line_to(point(spawn.direction.x * 20.0, spawn.direction.y * 20.0));
+        commands
+            .spawn(
+                builder.build().stroke(
+                    material.clone(),
+                    &mut meshes,
+                    spawn.position.extend(2.0),
+                    &StrokeOptions::default()
+                        .with_line_width(2.0)
+                        .with_line_cap(LineCap::Round),
+                ),
+            )
+            .with(EditorMark);
+    }
+    let outlines = editor
+        .selection_outline()
+        .into_iter()
+        .chain(editor.pending_pad.map(|center| (center, 14.0)));
+    for (center, radius) in outlines {
+        commands
+            .spawn(primitive(
+                material.clone(),
+                &mut meshes,
+                ShapeType::Circle(radius),
+                TessellationMode::Stroke(&StrokeOptions::default().with_line_width(2.0)),
+                center.extend(2.0).into(),
+            ))
+            .with(EditorMark);
+    }
+}
+
+/// Spawn / despawn the ball entities to match the balls of the `Simulation`
+fn sync_ball_entities(
+    commands: &mut Commands,
+    sim: Res<Simulation>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query_balls: Query<(Entity, &BallRef)>,
+) {
+    let mut known = HashSet::new();
+    for (entity, ball_ref) in query_balls.iter() {
+        if sim.ball(ball_ref.0).is_some() {
+            known.insert(ball_ref.0);
+        } else {
+            commands.despawn(entity);
         }
     }
+    for ball in sim.balls.iter().filter(|ball| !known.contains(&ball.id)) {
+        let material = materials.add(Color::rgb(0.8, 0.0, 0.0).into());
+        commands
+            .spawn(primitive(
+                material,
+                &mut meshes,
+                ShapeType::Circle(ball.radius),
+                TessellationMode::Fill(&FillOptions::default()),
+                ball.position.extend(1.0).into(),
+            ))
+            .with(BallRef(ball.id));
+    }
 }
 
-fn start_control_system(
+fn zone_color(kind: PowerUpKind) -> Color {
+    match kind {
+        PowerUpKind::DoubleScore => Color::rgba(0.9, 0.7, 0.0, 0.6),
+        PowerUpKind::SlowBall => Color::rgba(0.2, 0.4, 0.9, 0.6),
+        PowerUpKind::AutoPaddle => Color::rgba(0.1, 0.7, 0.3, 0.6),
+        PowerUpKind::FastBall => Color::rgba(0.9, 0.1, 0.1, 0.6),
+        PowerUpKind::Magnetic => Color::rgba(0.6, 0.2, 0.8, 0.6),
+        PowerUpKind::Multiball => Color::rgba(0.9, 0.4, 0.7, 0.6),
+    }
+}
+
+/// Spawn / despawn the zone entities to match the power-up zones of the `Simulation`
+fn sync_zone_entities(
+    commands: &mut Commands,
+    sim: Res<Simulation>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query_zones: Query<(Entity, &ZoneRef)>,
+) {
+    let mut known = HashSet::new();
+    for (entity, zone_ref) in query_zones.iter() {
+        if sim.power_ups.zones.iter().any(|zone| zone.id == zone_ref.0) {
+            known.insert(zone_ref.0);
+        } else {
+            commands.despawn(entity);
+        }
+    }
+    for zone in sim
+        .power_ups
+        .zones
+        .iter()
+        .filter(|zone| !known.contains(&zone.id))
+    {
+        let material = materials.add(zone_color(zone.kind).into());
+        commands
+            .spawn(primitive(
+                material,
+                &mut meshes,
+                ShapeType::Circle(zone.radius),
+                TessellationMode::Fill(&FillOptions::default()),
+                zone.position.extend(0.5).into(),
+            ))
+            .with(ZoneRef(zone.id));
+    }
+}
+
+fn game_state_control_system(
     mouse_button_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
     mut game_state_events: ResMut<Events<GameStateEvent>>,
     gamepad_manager: ResMut<GamepadState>,
     gamepad_inputs: Res<Input<GamepadButton>>,
@@ -374,6 +1586,15 @@ fn start_control_system(
         // eprintln!("{:?}", event);
         game_state_events.send(GameStateEvent::Start)
     }
+    if keyboard_input.just_pressed(KeyCode::P) {
+        game_state_events.send(GameStateEvent::TogglePause)
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        game_state_events.send(GameStateEvent::BackToTitle)
+    }
+    if keyboard_input.just_pressed(KeyCode::E) {
+        game_state_events.send(GameStateEvent::Edit)
+    }
     for gamepad in gamepad_manager.gamepads.iter() {
         if gamepad_inputs.just_released(GamepadButton(*gamepad, GamepadButtonType::South)) {
             // eprintln!(
@@ -382,26 +1603,33 @@ fn start_control_system(
             // );
             game_state_events.send(GameStateEvent::Start)
         }
+        if gamepad_inputs.just_released(GamepadButton(*gamepad, GamepadButtonType::Start)) {
+            game_state_events.send(GameStateEvent::TogglePause)
+        }
+        if gamepad_inputs.just_released(GamepadButton(*gamepad, GamepadButtonType::Select)) {
+            game_state_events.send(GameStateEvent::BackToTitle)
+        }
     }
 }
 
 fn paddle_control_by_mouse_system(
     mut state: ResMut<State>,
     cursor_moved_events: Res<Events<CursorMoved>>,
+    mouse_button_input: Res<Input<MouseButton>>,
     wnds: Res<Windows>,
-    mut query: Query<&mut Paddle>,
+    mut controllers: ResMut<DeviceControllers>,
     // query to get camera Bundle
     q_camera: Query<(&Camera, &Transform)>,
 ) {
     if let Ok((_, camera_transform)) = q_camera.get(state.camera_e) {
         for ev in state.cursor_moved_event_reader.iter(&cursor_moved_events) {
             let pos_wld = find_mouse_position(ev, &wnds, &camera_transform);
-            for mut paddle in query.iter_mut() {
-                let mouse_angle = pos_wld.y.atan2(pos_wld.x);
-                paddle.set_angle(mouse_angle);
-            }
+            controllers.mouse.cursor = Some(pos_wld.truncate().truncate());
         }
     }
+    if mouse_button_input.just_pressed(MouseButton::Left) {
+        controllers.mouse.release = true;
+    }
 }
 
 // see [Convert screen coordinates to world coordinates](https://github.com/jamadazi/bevy-cookbook/blob/master/bevy-cookbook.md#convert-screen-coordinates-to-world-coordinates)
@@ -444,153 +1672,439 @@ fn gamepad_connection_system(
 fn paddle_control_by_gamepad_system(
     gamepad_manager: Res<GamepadState>,
     axes: Res<Axis<GamepadAxis>>,
-    mut query: Query<&mut Paddle>,
+    gamepad_inputs: Res<Input<GamepadButton>>,
+    mut controllers: ResMut<DeviceControllers>,
 ) {
     for gamepad in gamepad_manager.gamepads.iter().cloned() {
-        let maybe_x = axes
-            .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickX))
-            //.filter(|value| (value - 1.0f32).abs() > 0.01f32 && (value + 1.0f32).abs() > 0.01f32)
-            ;
-        let maybe_y = axes
-            .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickY))
-            //.filter(|value| (value - 1.0f32).abs() > 0.01f32 && (value + 1.0f32).abs() > 0.01f32)
-            ;
-        if let Some((x, y)) = maybe_x.zip(maybe_y) {
-            // ignore if x and y are in the dead zone
-            if x.abs() > 0.03f32 && y.abs() > 0.03f32 {
-                let angle = y.atan2(x);
-                for mut paddle in query.iter_mut() {
-                    // eprintln!("rot via gamepad:  {:?}", rot);
-                    paddle.set_angle(angle);
-                }
+        if gamepad_inputs.just_pressed(GamepadButton(gamepad, GamepadButtonType::South)) {
+            for device in &[Device::LeftStick(gamepad), Device::RightStick(gamepad)] {
+                controllers.sticks.entry(*device).or_default().release = true;
             }
         }
+        if let Some(stick) = find_stick_position(
+            &axes,
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+        ) {
+            // eprintln!("rot via gamepad:  {:?}", rot);
+            controllers
+                .sticks
+                .entry(Device::LeftStick(gamepad))
+                .or_default()
+                .stick = Some(stick);
+        }
+        if let Some(stick) = find_stick_position(
+            &axes,
+            gamepad,
+            GamepadAxisType::RightStickX,
+            GamepadAxisType::RightStickY,
+        ) {
+            controllers
+                .sticks
+                .entry(Device::RightStick(gamepad))
+                .or_default()
+                .stick = Some(stick);
+        }
     }
 }
 
-fn find_ball_paddle_collision_point(
-    ball_translation_current: &Vec3,
-    ball_translation_previous: &Vec3,
-    ball: &Ball,
-    paddle: &Paddle,
-) -> Option<(Vec3, f32)> {
-    let current_o_dist = ball_translation_current.length();
-    let previous_o_dist = ball_translation_previous.length();
-    let mvt_dir = (current_o_dist - previous_o_dist).signum();
-    let range = paddle.half_height + ball.radius;
-    let paddle_o_dist = paddle.radius_origin - mvt_dir * range;
-    let maybe_collision_o_dist =
-        if previous_o_dist < paddle_o_dist && paddle_o_dist <= current_o_dist {
-            Some(paddle_o_dist)
-        } else if previous_o_dist > paddle_o_dist && paddle_o_dist >= current_o_dist {
-            Some(paddle_o_dist)
-        } else {
-            None
+fn find_stick_position(
+    axes: &Axis<GamepadAxis>,
+    gamepad: Gamepad,
+    axis_x: GamepadAxisType,
+    axis_y: GamepadAxisType,
+) -> Option<Vec2> {
+    let maybe_x = axes
+        .get(GamepadAxis(gamepad, axis_x))
+        //.filter(|value| (value - 1.0f32).abs() > 0.01f32 && (value + 1.0f32).abs() > 0.01f32)
+        ;
+    let maybe_y = axes
+        .get(GamepadAxis(gamepad, axis_y))
+        //.filter(|value| (value - 1.0f32).abs() > 0.01f32 && (value + 1.0f32).abs() > 0.01f32)
+        ;
+    maybe_x
+        .zip(maybe_y)
+        // ignore if x and y are in the dead zone
+        .filter(|(x, y)| x.abs() > 0.03f32 && y.abs() > 0.03f32)
+        .map(|(x, y)| Vec2::new(x, y))
+}
+
+fn paddle_control_by_keyboard_system(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut controllers: ResMut<DeviceControllers>,
+) {
+    let mut direction = 0.0;
+    if keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::A) {
+        direction += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::Right) || keyboard_input.pressed(KeyCode::D) {
+        direction -= 1.0;
+    }
+    // the rotation is accumulated until the next step
+    controllers.keyboard.rotation += direction * KEYBOARD_ANGLE_SPEED * time.delta_seconds();
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        controllers.keyboard.release = true;
+    }
+}
+
+fn control_mode_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut control_mode: ResMut<ControlMode>,
+    linked_offset: Res<LinkedOffset>,
+    mut play_mode: ResMut<PlayMode>,
+    mut difficulty: ResMut<Difficulty>,
+    mut multiball_mode: ResMut<MultiballMode>,
+    mut controllers: ResMut<DeviceControllers>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        controllers.ai = match controllers.ai {
+            Some(_) => None,
+            None => Some(AiController::default()),
         };
-    maybe_collision_o_dist.and_then(|collision_o_dist| {
-        let ratio = (collision_o_dist - previous_o_dist) / (current_o_dist - previous_o_dist);
-        let collision_point = *ball_translation_previous
-            + ((*ball_translation_current - *ball_translation_previous).normalize() * ratio);
-        let collision_rot = positive_angle(collision_point.y.atan2(collision_point.x));
-        let paddle_rot = paddle.angle_origin;
-        if (collision_rot - paddle_rot).abs() <= paddle.half_surface_angle {
-            Some((collision_point, ratio))
-        } else {
-            None
-        }
-    })
+    }
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        *control_mode = control_mode.next(linked_offset.0);
+    }
+    // applied at the next start
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        *play_mode = play_mode.next();
+    }
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        multiball_mode.0 = !multiball_mode.0;
+    }
+    if keyboard_input.just_pressed(KeyCode::F7) {
+        *difficulty = difficulty.next();
+    }
 }
 
-fn positive_angle(angle: f32) -> f32 {
-    let a = (angle + (2.0 * PI)) % (2.0 * PI);
-    a
+fn bindings_system(
+    play_mode: Res<PlayMode>,
+    gamepad_manager: Res<GamepadState>,
+    mut bindings: ResMut<Bindings>,
+) {
+    let mut gamepads: Vec<Gamepad> = gamepad_manager.gamepads.iter().cloned().collect();
+    gamepads.sort_by_key(|gamepad| gamepad.0);
+    *bindings = Bindings::new(*play_mode, &gamepads);
 }
 
 fn ball_movement_system(
     commands: &mut Commands,
     time: Res<Time>,
-    mut ball_query: Query<(&mut Ball, &mut Transform)>,
-    paddle_query: Query<(Entity, &Paddle)>,
-) {
-    // clamp the timestep to stop the ball from escaping when the game starts
-    let delta_seconds = f32::max(1.0 / 60.0, f32::min(1.0, time.delta_seconds()));
-
-    for (mut ball, mut transform) in ball_query.iter_mut() {
-        let ball_translation_previous = transform.translation;
-        transform.translation += (ball.velocity() * delta_seconds) * ball.mvt_dir;
-        for (entity, paddle) in paddle_query.iter() {
-            commands.remove_one::<Hit>(entity);
-            let maybe_collision_point = find_ball_paddle_collision_point(
-                &transform.translation,
-                &ball_translation_previous,
-                &ball,
-                paddle,
-            );
-            if let Some((collision_point, ratio)) = maybe_collision_point {
-                commands.insert_one(
-                    entity,
-                    Hit {
-                        direction: ball.mvt_dir,
-                    },
-                );
-                let normal_surface =
-                    Vec3::new(-collision_point.x, -collision_point.y, 0.0).normalize();
-                let speed_impact = 1.0 * paddle.angle_speed / (delta_seconds * 2.0 * PI);
-                let mirror = normal_surface
-                    + Vec3::new(
-                        -normal_surface.y * speed_impact,
-                        normal_surface.x * speed_impact,
-                        0.0,
-                    );
-                let mvt_dir = reflect_2d(ball.mvt_dir, mirror.normalize());
-                ball.mvt_dir = mvt_dir;
-                ball.velocity_indicator += 1;
-                transform.translation = collision_point
-                    + ((1.0 - ratio) * (ball.velocity() * delta_seconds)) * ball.mvt_dir;
+    mut timestep: ResMut<FixedTimestep>,
+    mut sim: ResMut<Simulation>,
+    control_mode: Res<ControlMode>,
+    bindings: Res<Bindings>,
+    mut controllers: ResMut<DeviceControllers>,
+    difficulty: Res<Difficulty>,
+    mut recording: ResMut<Recording>,
+    mut playback: ResMut<Playback>,
+    game_state: Res<GameState>,
+    mut game_state_events: ResMut<Events<GameStateEvent>>,
+    paddle_query: Query<(Entity, &PaddleRef)>,
+) {
+    // with one player per paddle (or the AI), the paddles can't be linked
+    let control_mode = if sim.play_mode.players() > 1 || controllers.ai.is_some() {
+        ControlMode::Independent
+    } else {
+        *control_mode
+    };
+    let mut events = vec![];
+    if game_state.is_running() {
+        if recording.0.is_none() && playback.0.is_none() {
+            recording.0 = Some(Replay::record_from(
+                &sim,
+                *difficulty,
+                timestep.step_seconds,
+            ));
+        }
+        // the simulation always runs with the same delta, so the trajectory doesn't depend on the frame rate
+        for _ in 0..timestep.advance(time.delta_seconds()) {
+            let requested = match playback.0.as_mut() {
+                Some(player) if player.is_finished(sim.tick) => break,
+                Some(player) => controlled_inputs(player, &sim),
+                None => {
+                    let inputs = controllers.requested(&bindings, &sim);
+                    Inputs {
+                        outer_release: inputs.outer_release,
+                        inner_release: inputs.inner_release,
+                        ..control_mode.dispatch(inputs.outer_angle, inputs.inner_angle)
+                    }
+                }
+            };
+            let step_events = sim.step(timestep.step_seconds, &requested);
+            if let Some(replay) = recording.0.as_mut() {
+                replay.record(sim.tick, &requested);
+            }
+            let game_over = step_events.iter().any(|e| matches!(e, Event::GameOver));
+            events.extend(step_events);
+            if game_over {
+                break;
             }
         }
+    } else {
+        // the requests are consumed, even if they are not applied
+        let inputs = controllers.requested(&bindings, &sim);
+        if game_state.accepts_paddle_inputs() && playback.0.is_none() {
+            sim.apply_inputs(&control_mode.dispatch(inputs.outer_angle, inputs.inner_angle));
+        }
     }
-}
-
-fn reflect_2d(v: Vec3, n: Vec3) -> Vec3 {
-    let d = v.x * n.x + v.y * n.y; //dot(v, n)
-    Vec3::new(v.x - 2.0 * d * n.x, v.y - 2.0 * d * n.y, 0.0)
-}
-
-fn hit_as_score(mut scoreboard: ResMut<Scoreboard>, paddle_query: Query<(&Paddle, &Hit)>) {
-    for (_paddle, _hit) in paddle_query.iter() {
-        scoreboard.score += 1;
+    for (entity, _) in paddle_query.iter() {
+        commands.remove_one::<Hit>(entity);
+    }
+    for event in events {
+        match event {
+            Event::Hit {
+                paddle, direction, ..
+            } => {
+                for (entity, paddle_ref) in paddle_query.iter() {
+                    if paddle_ref.0 == paddle {
+                        commands.insert_one(
+                            entity,
+                            Hit {
+                                direction: direction.extend(0.0),
+                            },
+                        );
+                    }
+                }
+            }
+            Event::Escaped { .. }
+            | Event::Point { .. }
+            | Event::Bounced { .. }
+            | Event::Teleported { .. }
+            | Event::Collected { .. }
+            | Event::Caught { .. }
+            | Event::Released { .. }
+            | Event::Split { .. }
+            | Event::Collided { .. } => (),
+            Event::GameOver => game_state_events.send(GameStateEvent::GameOver),
+        }
     }
 }
 
 fn scoreboard_system(
-    scoreboard: Res<Scoreboard>,
+    sim: Res<Simulation>,
     mut query_scoretext: Query<(&mut Text, &ScoreText)>,
     mut query_scorebesttext: Query<(&mut Text, &ScoreBestText)>,
 ) {
     for (mut text, _) in query_scoretext.iter_mut() {
-        text.value = format!("{}", scoreboard.score);
+        text.value = if sim.play_mode == PlayMode::Versus {
+            format!(
+                "{} : {}",
+                sim.scoreboard.versus.outer, sim.scoreboard.versus.inner
+            )
+        } else {
+            format!("{}", sim.scoreboard.score)
+        };
     }
     for (mut text, _) in query_scorebesttext.iter_mut() {
-        text.value = format!("Best: {}", scoreboard.best);
+        text.value = format!("Best: {}", sim.scoreboard.best);
     }
 }
 
-fn update_paddle_transform(mut paddle_query: Query<(&Paddle, &mut Transform)>) {
-    for (paddle, mut paddle_transform) in paddle_query.iter_mut() {
-        paddle_transform.rotation =
-            Quat::from_rotation_z(paddle.angle_origin - paddle.half_surface_angle);
+fn effects_text_system(
+    sim: Res<Simulation>,
+    timestep: Res<FixedTimestep>,
+    mut query_effectstext: Query<(&mut Text, &EffectsText)>,
+) {
+    let value = sim
+        .power_ups
+        .effects
+        .iter()
+        .map(|effect| {
+            let seconds = (effect.remaining_ticks as f32 * timestep.step_seconds).ceil();
+            match effect.role {
+                Some(role) => format!("{} ({:?}): {}s", effect.kind, role, seconds),
+                None => format!("{}: {}s", effect.kind, seconds),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    for (mut text, _) in query_effectstext.iter_mut() {
+        text.value = value.clone();
     }
 }
 
-fn update_paddle_fx(mut paddle_query: Query<(&Paddle, &ImpactFx, &mut Transform)>) {
+fn debug_overlay_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+    sim: Res<Simulation>,
+    game_state: Res<GameState>,
+    difficulty: Res<Difficulty>,
+    control_mode: Res<ControlMode>,
+    play_mode: Res<PlayMode>,
+    multiball_mode: Res<MultiballMode>,
+    bindings: Res<Bindings>,
+    controllers: Res<DeviceControllers>,
+    playback: Res<Playback>,
+    mut query_debugtext: Query<(&mut Text, &DebugText)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        overlay.visible = !overlay.visible;
+    }
+    let value = if overlay.visible {
+        let mut lines = vec![
+            format!("state: {:?}", *game_state),
+            format!("difficulty: {:?}", *difficulty),
+            format!("speed curve: {}", sim.speed_curve),
+            format!("control: {:?}", *control_mode),
+            format!("play mode: {:?} (next: {:?})", sim.play_mode, *play_mode),
+            format!(
+                "multiball: {} (next: {})",
+                sim.multiball.enabled, multiball_mode.0
+            ),
+            format!("seed: {}", sim.seed),
+        ];
+        if let Some(ai) = controllers.ai.as_ref() {
+            lines.push(format!(
+                "auto-paddle: reaction {} steps, error {} rad",
+                ai.reaction_ticks, ai.max_error
+            ));
+        }
+        if let Some(player) = playback.0.as_ref() {
+            lines.push(format!(
+                "replay: tick {} / {}",
+                sim.tick, player.replay.config.ticks
+            ));
+        }
+        for binding in bindings.0.iter() {
+            lines.push(format!(
+                "player {}: {:?} -> {:?}",
+                binding.player + 1,
+                binding.device,
+                binding.role
+            ));
+        }
+        for ball in sim.balls.iter() {
+            lines.push(format!(
+                "ball {}: {} impacts, velocity {:.0}",
+                ball.id.0,
+                ball.velocity_indicator,
+                ball.velocity(&sim.speed_curve)
+            ));
+        }
+        lines.join("\n")
+    } else {
+        "".to_string()
+    };
+    for (mut text, _) in query_debugtext.iter_mut() {
+        text.value = value.clone();
+    }
+}
+
+fn update_ball_transform(
+    sim: Res<Simulation>,
+    timestep: Res<FixedTimestep>,
+    mut ball_query: Query<(&BallRef, &mut Transform)>,
+) {
+    let alpha = timestep.alpha();
+    for (ball_ref, mut transform) in ball_query.iter_mut() {
+        if let Some(ball) = sim.ball(ball_ref.0) {
+            transform.translation = ball
+                .position_interpolated(alpha)
+                .extend(transform.translation.z);
+        }
+    }
+}
+
+fn update_paddle_transform(
+    sim: Res<Simulation>,
+    mut paddle_query: Query<(&PaddleRef, &mut Transform), Without<TrackPaddle>>,
+) {
+    // the mesh is the paddle at rest, the arena can pulse
+    let scale = sim.arena.scale();
+    for (paddle_ref, mut paddle_transform) in paddle_query.iter_mut() {
+        // the entities of a removed ring are despawned at the end of the frame
+        if let Some(paddle) = sim.paddles.get(paddle_ref.0) {
+            paddle_transform.rotation =
+                Quat::from_rotation_z(paddle.angle_origin - paddle.half_surface_angle);
+            paddle_transform.scale = Vec3::new(scale, scale, 1.0);
+        }
+    }
+}
+
+/// The rings turn and pulse with the arena (see `ArenaMotion`)
+fn update_ring_transform(sim: Res<Simulation>, mut ring_query: Query<(&RingRef, &mut Transform)>) {
+    let scale = sim.arena.scale();
+    for (_ring, mut transform) in ring_query.iter_mut() {
+        transform.rotation = Quat::from_rotation_z(sim.arena.rotation());
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
+
+/// The pads of the teleporters turn with the arena (see `ArenaMotion`)
+fn update_pad_transform(sim: Res<Simulation>, mut pad_query: Query<(&PadRef, &mut Transform)>) {
+    for (pad_ref, mut transform) in pad_query.iter_mut() {
+        if let Some(teleporter) = sim.teleporters.get(pad_ref.teleporter) {
+            let pad = teleporter.pad_now(pad_ref.pad);
+            transform.translation = pad.center.extend(transform.translation.z);
+            transform.rotation = Quat::from_rotation_z(teleporter.arena_angle);
+        }
+    }
+}
+
+/// The shape of a paddle on a track changes along the track: rebuild its mesh when it moves
+fn update_track_paddle_mesh(
+    sim: Res<Simulation>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut paddle_query: Query<(&PaddleRef, &mut TrackPaddle, &mut Handle<Mesh>)>,
+) {
+    for (paddle_ref, mut track_paddle, mut mesh) in paddle_query.iter_mut() {
+        let paddle = match sim.paddles.get(paddle_ref.0) {
+            Some(paddle) => paddle,
+            None => continue,
+        };
+        let now = (paddle.angle_origin, sim.arena.rotation(), sim.arena.scale());
+        if track_paddle.drawn == Some(now) {
+            continue;
+        }
+        if let Some((track, center, half_length)) = paddle.track_surface() {
+            let points = track.portion(center - half_length, center + half_length);
+            *mesh = polyline(&points, false)
+                .build()
+                .stroke(
+                    Handle::default(),
+                    &mut meshes,
+                    Vec3::new(0.0, 0.0, 0.0),
+                    &paddle_stroke_options(paddle.half_height * 2.0),
+                )
+                .mesh;
+            track_paddle.drawn = Some(now);
+        }
+    }
+}
+
+fn update_obstacle_transform(
+    sim: Res<Simulation>,
+    mut obstacle_query: Query<(&ObstacleRef, &mut Transform)>,
+) {
+    for (obstacle_ref, mut transform) in obstacle_query.iter_mut() {
+        if let Some(obstacle) = sim.obstacles.get(obstacle_ref.0) {
+            match obstacle.shape_now() {
+                // the mesh of a bumper is centered on the bumper
+                Shape::Bumper { center, .. } => {
+                    transform.translation = center.extend(transform.translation.z);
+                }
+                // the mesh of a deflector is in the coordinates of the ring
+                Shape::Deflector { .. } => {
+                    let (angle, translation) = obstacle.displacement();
+                    transform.rotation = Quat::from_rotation_z(angle);
+                    transform.translation = translation.extend(transform.translation.z);
+                }
+            }
+        }
+    }
+}
+
+fn update_paddle_fx(mut paddle_query: Query<(&PaddleRef, &ImpactFx, &mut Transform)>) {
     for (_paddle, impact, mut paddle_transform) in paddle_query.iter_mut() {
         paddle_transform.translation = impact.mvt;
         dbg!(paddle_transform.translation);
     }
 }
 
-fn hit_to_fx(commands: &mut Commands, paddle_query: Query<(Entity, &Paddle, &Hit)>) {
+fn hit_to_fx(commands: &mut Commands, paddle_query: Query<(Entity, &PaddleRef, &Hit)>) {
     for (entity, _paddle, hit) in paddle_query.iter() {
         let impact = ImpactFx { mvt: Vec3::zero() };
         let e_impact = impact
@@ -616,17 +2130,3 @@ fn hit_to_fx(commands: &mut Commands, paddle_query: Query<(Entity, &Paddle, &Hit
         commands.insert_one(entity, e_impact);
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_positive_angle() {
-        assert_eq!(positive_angle(0.0), 0.0);
-        assert_eq!(positive_angle(2.0 * PI), 0.0);
-        assert_eq!(positive_angle(PI), PI);
-        assert_eq!(positive_angle(-PI), PI);
-        assert_eq!(positive_angle(-0.5 * PI), 1.5 * PI);
-    }
-}
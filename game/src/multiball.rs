@@ -0,0 +1,123 @@
+//! Extra balls in play at the same time, from the power-up `Multiball` or on score milestones.
+
+use crate::ball::Ball;
+use crate::physics::reflect_2d;
+use crate::speed_curve::SpeedCurve;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Multiball {
+    /// without, there is only one ball in play (the historical game)
+    pub enabled: bool,
+    /// an extra ball every `every_points` points, 0 for none
+    pub every_points: usize,
+    /// no extra ball when there is already `max_balls` balls
+    pub max_balls: usize,
+}
+
+impl Default for Multiball {
+    fn default() -> Self {
+        Multiball {
+            enabled: false,
+            every_points: 20,
+            max_balls: 4,
+        }
+    }
+}
+
+impl Multiball {
+    /// The next score that gives an extra ball, after `score`.
+    pub fn next_milestone(&self, score: usize) -> Option<usize> {
+        if !self.enabled || self.every_points == 0 {
+            None
+        } else {
+            Some((score / self.every_points + 1) * self.every_points)
+        }
+    }
+}
+
+/// Bounce 2 balls that touch each other and come closer, like on a bumper (each ball keeps its
+/// velocity), then move them apart so they don't overlap. Return `true` if they collided.
+pub fn collide_balls(a: &mut Ball, b: &mut Ball, speed_curve: &SpeedCurve) -> bool {
+    let delta = b.position - a.position;
+    let distance = delta.length();
+    let range = a.radius + b.radius;
+    if distance >= range || distance == 0.0 {
+        return false;
+    }
+    // normal from a to b
+    let normal = delta / distance;
+    let relative_velocity =
+        b.mvt_dir * b.velocity(speed_curve) - a.mvt_dir * a.velocity(speed_curve);
+    if relative_velocity.dot(normal) >= 0.0 {
+        return false;
+    }
+    if a.mvt_dir.dot(normal) > 0.0 {
+        a.mvt_dir = reflect_2d(a.mvt_dir, normal);
+    }
+    if b.mvt_dir.dot(normal) < 0.0 {
+        b.mvt_dir = reflect_2d(b.mvt_dir, normal);
+    }
+    let overlap = (range - distance) / 2.0;
+    a.position -= normal * overlap;
+    b.position += normal * overlap;
+    true
+}
+
+/// The direction of a new ball split from a ball moving along `direction`.
+pub fn split_direction(direction: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(
+        direction.x * cos - direction.y * sin,
+        direction.x * sin + direction.y * cos,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ball::BallId;
+
+    #[test]
+    fn test_next_milestone() {
+        assert_eq!(Multiball::default().next_milestone(12), None);
+        let multiball = Multiball {
+            enabled: true,
+            every_points: 10,
+            max_balls: 3,
+        };
+        assert_eq!(multiball.next_milestone(0), Some(10));
+        assert_eq!(multiball.next_milestone(10), Some(20));
+        assert_eq!(multiball.next_milestone(19), Some(20));
+    }
+
+    #[test]
+    fn test_collide_balls() {
+        let curve = SpeedCurve::default();
+        // head-on
+        let mut a = Ball::new(BallId(0), Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0));
+        let mut b = Ball::new(BallId(1), Vec2::new(8.0, 0.0), Vec2::new(-1.0, 0.0));
+        assert!(collide_balls(&mut a, &mut b, &curve));
+        assert_eq!(a.mvt_dir, Vec2::new(-1.0, 0.0));
+        assert_eq!(b.mvt_dir, Vec2::new(1.0, 0.0));
+        assert!((b.position - a.position).length() >= 10.0 - 1e-5);
+        // already moving apart
+        assert!(!collide_balls(&mut a, &mut b, &curve));
+        // a ball catches up with an other one
+        let mut a = Ball::new(BallId(0), Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0));
+        let mut b = Ball::new(BallId(1), Vec2::new(8.0, 0.0), Vec2::new(0.0, 1.0));
+        assert!(collide_balls(&mut a, &mut b, &curve));
+        assert_eq!(a.mvt_dir, Vec2::new(-1.0, 0.0));
+        assert_eq!(b.mvt_dir, Vec2::new(0.0, 1.0));
+        // balls going apart, even if one is faster
+        let mut a = Ball::new(BallId(0), Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0));
+        let mut b = Ball::new(BallId(1), Vec2::new(8.0, 0.0), Vec2::new(1.0, 0.0));
+        b.velocity_indicator = 10;
+        assert!(!collide_balls(&mut a, &mut b, &curve));
+        // too far
+        let mut a = Ball::new(BallId(0), Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0));
+        let mut b = Ball::new(BallId(1), Vec2::new(11.0, 0.0), Vec2::new(-1.0, 0.0));
+        assert!(!collide_balls(&mut a, &mut b, &curve));
+    }
+}
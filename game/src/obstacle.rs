@@ -0,0 +1,295 @@
+//! Obstacles placed between the rings: circular bumpers and straight deflectors, static or moving.
+//!
+//! They reflect the balls like the paddles do (see `reflect_on_moving`, they can move), can boost the balls and score points.
+
+use crate::physics::{find_circle_collision, find_segment_collision, Collision};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Half of the thickness of a deflector.
+pub const DEFLECTOR_HALF_WIDTH: f32 = 2.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    Bumper {
+        center: Vec2,
+        radius: f32,
+    },
+    /// both sides of the segment reflect the balls
+    Deflector {
+        start: Vec2,
+        end: Vec2,
+    },
+}
+
+impl Shape {
+    /// The shape rotated by `angle` around the center of the ring, then moved by `translation`.
+    pub fn displaced(&self, angle: f32, translation: Vec2) -> Shape {
+        let (sin, cos) = angle.sin_cos();
+        let displace =
+            |p: &Vec2| Vec2::new(p.x * cos - p.y * sin, p.x * sin + p.y * cos) + translation;
+        match self {
+            Shape::Bumper { center, radius } => Shape::Bumper {
+                center: displace(center),
+                radius: *radius,
+            },
+            Shape::Deflector { start, end } => Shape::Deflector {
+                start: displace(start),
+                end: displace(end),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Motion {
+    #[default]
+    Static,
+    /// turns around the center of the ring (radian per second, counterclockwise)
+    Orbit { angular_speed: f32 },
+    /// moves smoothly to `offset` and back, in `period` seconds
+    Oscillate { offset: Vec2, period: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    /// the shape at the start of the round
+    pub shape: Shape,
+    #[serde(default)]
+    pub motion: Motion,
+    /// impacts added to the `velocity_indicator` of the ball by a hit (see `SpeedCurve`)
+    #[serde(default)]
+    pub boost: i32,
+    /// points scored by a hit
+    #[serde(default)]
+    pub points: usize,
+    /// seconds since the start of the round
+    #[serde(skip)]
+    pub elapsed: f32,
+    /// rotation of the arena (see `ArenaMotion`), the obstacle turns with it
+    #[serde(skip)]
+    pub arena_angle: f32,
+}
+
+impl Obstacle {
+    pub fn bumper(center: Vec2, radius: f32) -> Self {
+        Obstacle::new(Shape::Bumper { center, radius })
+    }
+
+    pub fn deflector(start: Vec2, end: Vec2) -> Self {
+        Obstacle::new(Shape::Deflector { start, end })
+    }
+
+    fn new(shape: Shape) -> Self {
+        Obstacle {
+            shape,
+            motion: Motion::Static,
+            boost: 0,
+            points: 0,
+            elapsed: 0.0,
+            arena_angle: 0.0,
+        }
+    }
+
+    pub fn advance(&mut self, delta_seconds: f32) {
+        self.elapsed += delta_seconds;
+    }
+
+    /// `(rotation around the center of the ring, translation)` of the shape since the start of the round.
+    pub fn displacement(&self) -> (f32, Vec2) {
+        let (angle, translation) = match self.motion {
+            Motion::Static => (0.0, Vec2::zero()),
+            Motion::Orbit { angular_speed } => (angular_speed * self.elapsed, Vec2::zero()),
+            Motion::Oscillate { offset, period } => {
+                let progress = if period > 0.0 {
+                    (1.0 - (2.0 * PI * self.elapsed / period).cos()) / 2.0
+                } else {
+                    0.0
+                };
+                (0.0, offset * progress)
+            }
+        };
+        if self.arena_angle == 0.0 {
+            return (angle, translation);
+        }
+        // then turned with the arena
+        let (sin, cos) = self.arena_angle.sin_cos();
+        (
+            angle + self.arena_angle,
+            Vec2::new(
+                translation.x * cos - translation.y * sin,
+                translation.x * sin + translation.y * cos,
+            ),
+        )
+    }
+
+    /// The velocity (per second) of the point of the obstacle at `point`, by its own motion (the
+    /// rotation of the arena isn't included).
+    pub fn velocity_at(&self, point: Vec2) -> Vec2 {
+        match self.motion {
+            Motion::Static => Vec2::zero(),
+            Motion::Orbit { angular_speed } => Vec2::new(-point.y, point.x) * angular_speed,
+            Motion::Oscillate { offset, period } if period > 0.0 => {
+                let pulsation = 2.0 * PI / period;
+                let (sin, cos) = self.arena_angle.sin_cos();
+                let offset = Vec2::new(
+                    offset.x * cos - offset.y * sin,
+                    offset.x * sin + offset.y * cos,
+                );
+                offset * ((pulsation * self.elapsed).sin() * pulsation / 2.0)
+            }
+            Motion::Oscillate { .. } => Vec2::zero(),
+        }
+    }
+
+    /// The shape at the current time.
+    pub fn shape_now(&self) -> Shape {
+        let (angle, translation) = self.displacement();
+        self.shape.displaced(angle, translation)
+    }
+
+    /// Collision with a ball (of `ball_radius`) moving from `previous` to `current`.
+    pub fn find_collision(
+        &self,
+        previous: Vec2,
+        current: Vec2,
+        ball_radius: f32,
+    ) -> Option<Collision> {
+        match self.shape_now() {
+            Shape::Bumper { center, radius } => {
+                find_circle_collision(previous, current, center, radius + ball_radius)
+            }
+            Shape::Deflector { start, end } => find_segment_collision(
+                previous,
+                current,
+                start,
+                end,
+                DEFLECTOR_HALF_WIDTH + ball_radius,
+            ),
+        }
+    }
+}
+
+/// Some obstacles between the rings, until there are levels.
+pub fn default_obstacles() -> Vec<Obstacle> {
+    let polar = |distance: f32, angle: f32| Vec2::new(angle.cos(), angle.sin()) * distance;
+    vec![
+        Obstacle {
+            points: 2,
+            ..Obstacle::bumper(polar(200.0, 0.25 * PI), 12.0)
+        },
+        Obstacle {
+            points: 2,
+            ..Obstacle::bumper(polar(200.0, 1.25 * PI), 12.0)
+        },
+        Obstacle {
+            motion: Motion::Orbit { angular_speed: 0.3 },
+            boost: 2,
+            points: 5,
+            ..Obstacle::bumper(polar(170.0, 0.75 * PI), 8.0)
+        },
+        Obstacle {
+            motion: Motion::Oscillate {
+                offset: polar(40.0, 1.75 * PI),
+                period: 6.0,
+            },
+            points: 1,
+            ..Obstacle::deflector(polar(160.0, 1.70 * PI), polar(200.0, 1.80 * PI))
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{RADIUS_EXTERN, RADIUS_INTERN};
+
+    #[test]
+    fn test_default_obstacles_stay_between_the_paddles() {
+        let ball_radius = 5.0;
+        for mut obstacle in default_obstacles() {
+            for _ in 0..(120 * 30) {
+                obstacle.advance(1.0 / 120.0);
+                let (min, max) = match obstacle.shape_now() {
+                    Shape::Bumper { center, radius } => {
+                        (center.length() - radius, center.length() + radius)
+                    }
+                    Shape::Deflector { start, end } => (
+                        crate::physics::distance_to_segment(Vec2::zero(), start, end),
+                        start.length().max(end.length()),
+                    ),
+                };
+                // room for a ball between the obstacle and the paddles
+                assert!(min > RADIUS_INTERN + 2.0 + 2.0 * ball_radius);
+                assert!(max < RADIUS_EXTERN - 6.0 - 2.0 * ball_radius);
+            }
+        }
+    }
+
+    #[test]
+    fn test_moving_obstacles() {
+        let mut obstacle = Obstacle {
+            motion: Motion::Orbit {
+                angular_speed: 0.5 * PI,
+            },
+            ..Obstacle::bumper(Vec2::new(100.0, 0.0), 10.0)
+        };
+        obstacle.advance(1.0);
+        match obstacle.shape_now() {
+            Shape::Bumper { center, .. } => {
+                assert!((center - Vec2::new(0.0, 100.0)).length() < 1e-3)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let mut obstacle = Obstacle {
+            motion: Motion::Oscillate {
+                offset: Vec2::new(0.0, 20.0),
+                period: 2.0,
+            },
+            ..Obstacle::deflector(Vec2::new(100.0, 0.0), Vec2::new(120.0, 0.0))
+        };
+        obstacle.advance(1.0);
+        assert_eq!(obstacle.displacement(), (0.0, Vec2::new(0.0, 20.0)));
+        obstacle.advance(1.0);
+        assert!(obstacle.displacement().1.length() < 1e-3);
+    }
+
+    #[test]
+    fn test_collision_with_a_bumper() {
+        let obstacle = Obstacle::bumper(Vec2::new(100.0, 0.0), 10.0);
+        let collision = obstacle
+            .find_collision(Vec2::new(50.0, 0.0), Vec2::new(100.0, 0.0), 5.0)
+            .unwrap();
+        assert!((collision.point - Vec2::new(85.0, 0.0)).length() < 1e-3);
+        assert_eq!(collision.normal, Vec2::new(-1.0, 0.0));
+        assert!(obstacle
+            .find_collision(Vec2::new(50.0, 20.0), Vec2::new(100.0, 20.0), 5.0)
+            .is_none());
+    }
+
+    #[test]
+    fn test_collision_with_a_deflector() {
+        let obstacle = Obstacle::deflector(Vec2::new(100.0, -20.0), Vec2::new(100.0, 20.0));
+        // on the side of the segment, from both directions
+        let collision = obstacle
+            .find_collision(Vec2::new(80.0, 10.0), Vec2::new(110.0, 10.0), 5.0)
+            .unwrap();
+        assert!((collision.point - Vec2::new(93.0, 10.0)).length() < 1e-3);
+        assert_eq!(collision.normal, Vec2::new(-1.0, 0.0));
+        let collision = obstacle
+            .find_collision(Vec2::new(120.0, 0.0), Vec2::new(90.0, 0.0), 5.0)
+            .unwrap();
+        assert_eq!(collision.normal, Vec2::new(1.0, 0.0));
+        // on the end of the segment
+        let collision = obstacle
+            .find_collision(Vec2::new(100.0, 40.0), Vec2::new(100.0, 10.0), 5.0)
+            .unwrap();
+        assert!((collision.point - Vec2::new(100.0, 27.0)).length() < 1e-3);
+        assert_eq!(collision.normal, Vec2::new(0.0, 1.0));
+        // beside the end
+        assert!(obstacle
+            .find_collision(Vec2::new(80.0, 30.0), Vec2::new(120.0, 30.0), 5.0)
+            .is_none());
+    }
+}
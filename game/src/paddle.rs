@@ -0,0 +1,151 @@
+use crate::angular_interval::AngularInterval;
+use crate::physics::positive_angle;
+use crate::track::{Track, TrackShape};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PaddleRole {
+    Outer,
+    Inner,
+}
+
+impl PaddleRole {
+    pub fn opponent(&self) -> PaddleRole {
+        match self {
+            PaddleRole::Outer => PaddleRole::Inner,
+            PaddleRole::Inner => PaddleRole::Outer,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Paddle {
+    /// the player (input) that moves the paddle, the outermost ring is always `Outer` and the
+    /// innermost `Inner`, the rings between can be moved by either
+    pub role: PaddleRole,
+    pub radius_origin: f32,
+    /// `radius_origin` when the arena doesn't pulse (see `ArenaMotion`)
+    pub rest_radius: f32,
+    pub angle_origin: f32,
+    /// radian per second (counterclockwise), measured at every step and filtered (see `Spin`)
+    pub angular_velocity: f32,
+    pub half_surface_angle: f32,
+    pub half_height: f32,
+    /// added to the angle requested for `role` (radian)
+    pub offset: f32,
+    /// points scored by a hit
+    pub points: usize,
+    /// the paddle follows the balls by itself (see `PowerUps`)
+    pub auto: bool,
+    /// the paddle catches the balls (see `PowerUps`)
+    pub magnetic: bool,
+    /// the ring isn't the circle of `radius_origin`: the paddle slides along the track, it covers
+    /// the length `radius_origin * 2 * half_surface_angle` around the point of the track in the
+    /// direction `angle_origin`
+    pub track: Option<Track>,
+}
+
+impl Paddle {
+    pub fn new(role: PaddleRole, radius: f32, height: f32, surface_angle: f32) -> Self {
+        Paddle {
+            role,
+            radius_origin: radius,
+            rest_radius: radius,
+            half_surface_angle: surface_angle / 2.0,
+            half_height: height / 2.0,
+            angle_origin: 0.0,
+            angular_velocity: 0.0,
+            offset: 0.0,
+            points: 1,
+            auto: false,
+            magnetic: false,
+            track: None,
+        }
+    }
+
+    pub fn with_track(mut self, shape: TrackShape) -> Self {
+        self.track = Some(Track::new(shape));
+        self
+    }
+
+    /// Follow the arena turned by `rotation` and scaled by `scale` (see `ArenaMotion`), the angle
+    /// of the paddle doesn't change: the players aim it in the window.
+    pub fn place(&mut self, rotation: f32, scale: f32) {
+        self.radius_origin = self.rest_radius * scale;
+        if let Some(track) = self.track.as_mut() {
+            track.place(rotation, scale);
+        }
+    }
+
+    /// Where `point` is relative to the ring after the arena turned by `rotation` and scaled by
+    /// `scale` (see `place`): a point that was outside (or inside) the ring stays outside (or
+    /// inside) it. Only a track turns with the arena, a circle has the same surface.
+    pub fn follow_ring(&self, point: Vec2, rotation: f32, scale: f32) -> Vec2 {
+        let point = point * scale;
+        if self.track.is_none() || rotation == 0.0 {
+            return point;
+        }
+        let (sin, cos) = rotation.sin_cos();
+        Vec2::new(point.x * cos - point.y * sin, point.x * sin + point.y * cos)
+    }
+
+    pub fn set_angle(&mut self, angle: f32) {
+        self.angle_origin = positive_angle(angle);
+    }
+
+    /// The angles covered by the surface of the paddle.
+    pub fn surface(&self) -> AngularInterval {
+        AngularInterval::new(self.angle_origin, self.half_surface_angle)
+    }
+
+    /// The arc lengths (on the track) of the center of the paddle and of half of its surface.
+    pub fn track_surface(&self) -> Option<(&Track, f32, f32)> {
+        self.track.as_ref().map(|track| {
+            (
+                track,
+                track.arc_length_at_angle(self.angle_origin),
+                self.half_surface_angle * self.radius_origin,
+            )
+        })
+    }
+
+    /// The distance between the center and the ring, in the direction of `point`.
+    pub fn radius_at(&self, point: Vec2) -> f32 {
+        match self.track.as_ref() {
+            Some(track) => track.radius_at(point.y.atan2(point.x)),
+            None => self.radius_origin,
+        }
+    }
+
+    /// The min and the max distance between the center and the ring.
+    pub fn radius_range(&self) -> (f32, f32) {
+        match self.track.as_ref() {
+            Some(track) => track.radius_range(),
+            None => (self.radius_origin, self.radius_origin),
+        }
+    }
+
+    /// How far `point` is from the ring (along the ray from the center).
+    pub fn distance_to_ring(&self, point: Vec2) -> f32 {
+        (point.length() - self.radius_at(point)).abs()
+    }
+
+    /// What a caught ball at `point` keeps (see `Attachment`): its distance to the center, or
+    /// its distance to the track (along the ray from the center) for a track.
+    pub fn attach_distance(&self, point: Vec2) -> f32 {
+        match self.track.as_ref() {
+            Some(_) => point.length() - self.radius_at(point),
+            None => point.length(),
+        }
+    }
+
+    /// The position of a caught ball, in the direction `angle` (see `attach_distance`).
+    pub fn carry(&self, angle: f32, distance: f32) -> Vec2 {
+        let direction = Vec2::new(angle.cos(), angle.sin());
+        match self.track.as_ref() {
+            Some(track) => direction * (track.radius_at(angle) + distance),
+            None => direction * distance,
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::ball::Ball;
+use crate::paddle::Paddle;
+use glam::Vec2;
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collision {
+    /// position of the (center of the) ball at the impact
+    pub point: Vec2,
+    /// fraction of the movement done before the impact
+    pub ratio: f32,
+    /// normal of the surface at the impact, oriented toward the ball
+    pub normal: Vec2,
+}
+
+/// The paddle is a thick arc: every point at less than `half_height` of its arc (so with
+/// rounded end caps). The ball collides when its center comes at less than `half_height + ball.radius`
+/// of the arc, either on the inner / outer side of the band or on one of the end caps.
+/// On a track, the arc is the portion of the track covered by the paddle (see `Track::find_collision`).
+pub fn find_ball_paddle_collision_point(
+    ball_translation_current: &Vec2,
+    ball_translation_previous: &Vec2,
+    ball: &Ball,
+    paddle: &Paddle,
+) -> Option<Collision> {
+    let range = paddle.half_height + ball.radius;
+    if let Some((track, center, half_length)) = paddle.track_surface() {
+        return track.find_collision(
+            *ball_translation_previous,
+            *ball_translation_current,
+            center,
+            half_length,
+            range,
+        );
+    }
+    let previous = *ball_translation_previous;
+    let mvt = *ball_translation_current - previous;
+    let surface = paddle.surface();
+    let mut best: Option<Collision> = None;
+
+    // inner or outer side of the band, depending on where the ball comes from
+    let previous_o_dist = previous.length();
+    if (previous_o_dist - paddle.radius_origin).abs() > range {
+        let side = (previous_o_dist - paddle.radius_origin).signum();
+        let band_o_dist = paddle.radius_origin + side * range;
+        if let Some(ratio) = find_circle_crossing(previous, mvt, Vec2::new(0.0, 0.0), band_o_dist) {
+            let point = previous + mvt * ratio;
+            if surface.contains(point.y.atan2(point.x)) {
+                best = Some(Collision {
+                    point,
+                    ratio,
+                    normal: point.normalize() * side,
+                });
+            }
+        }
+    }
+
+    // end caps
+    for end_angle in &[
+        surface.center - surface.half_width,
+        surface.center + surface.half_width,
+    ] {
+        let end = Vec2::new(end_angle.cos(), end_angle.sin()) * paddle.radius_origin;
+        if (previous - end).length() <= range {
+            continue;
+        }
+        if let Some(ratio) = find_circle_crossing(previous, mvt, end, range) {
+            if best.is_none_or(|collision| ratio < collision.ratio) {
+                let point = previous + mvt * ratio;
+                best = Some(Collision {
+                    point,
+                    ratio,
+                    normal: (point - end).normalize(),
+                });
+            }
+        }
+    }
+    best
+}
+
+/// Collision of a ball moving from `previous` to `current` with a disk of `range` (radius of the
+/// disk + radius of the ball). A ball already overlapping the disk doesn't collide.
+pub fn find_circle_collision(
+    previous: Vec2,
+    current: Vec2,
+    center: Vec2,
+    range: f32,
+) -> Option<Collision> {
+    if (previous - center).length() <= range {
+        return None;
+    }
+    find_circle_crossing(previous, current - previous, center, range).map(|ratio| {
+        let point = previous + (current - previous) * ratio;
+        Collision {
+            point,
+            ratio,
+            normal: (point - center).normalize(),
+        }
+    })
+}
+
+/// Collision of a ball moving from `previous` to `current` with the segment from `start` to `end`,
+/// thickened by `range` (half width of the segment + radius of the ball), so with rounded ends.
+pub fn find_segment_collision(
+    previous: Vec2,
+    current: Vec2,
+    start: Vec2,
+    end: Vec2,
+    range: f32,
+) -> Option<Collision> {
+    let mvt = current - previous;
+    let segment = end - start;
+    let length_squared = segment.dot(segment);
+    if length_squared == 0.0 {
+        return find_circle_collision(previous, current, start, range);
+    }
+    let mut best: Option<Collision> = None;
+
+    // side of the segment, the one where the ball comes from
+    let normal = Vec2::new(-segment.y, segment.x).normalize();
+    let previous_dist = (previous - start).dot(normal);
+    let approach = mvt.dot(normal);
+    if previous_dist.abs() > range && approach * previous_dist < 0.0 {
+        let side = previous_dist.signum();
+        let ratio = (side * range - previous_dist) / approach;
+        if (0.0..=1.0).contains(&ratio) {
+            let point = previous + mvt * ratio;
+            let along = (point - start).dot(segment) / length_squared;
+            if (0.0..=1.0).contains(&along) {
+                best = Some(Collision {
+                    point,
+                    ratio,
+                    normal: normal * side,
+                });
+            }
+        }
+    }
+
+    // ends
+    for end_point in &[start, end] {
+        if let Some(collision) = find_circle_collision(previous, current, *end_point, range) {
+            if best.is_none_or(|best| collision.ratio < best.ratio) {
+                best = Some(collision);
+            }
+        }
+    }
+    best
+}
+
+/// First ratio (in [0, 1]) of `mvt` where `start + mvt * ratio` crosses the circle.
+fn find_circle_crossing(start: Vec2, mvt: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let f = start - center;
+    let a = mvt.dot(mvt);
+    if a == 0.0 {
+        return None;
+    }
+    let b = 2.0 * f.dot(mvt);
+    let c = f.dot(f) - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt_discriminant = discriminant.sqrt();
+    // from outside, the first crossing is the entry, from inside it's the exit
+    let ratio = if c > 0.0 {
+        (-b - sqrt_discriminant) / (2.0 * a)
+    } else {
+        (-b + sqrt_discriminant) / (2.0 * a)
+    };
+    if (0.0..=1.0).contains(&ratio) {
+        Some(ratio)
+    } else {
+        None
+    }
+}
+
+pub fn positive_angle(angle: f32) -> f32 {
+    angle.rem_euclid(2.0 * PI)
+}
+
+/// Distance between `point` and the segment from `start` to `end`.
+pub fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.dot(segment);
+    let ratio = if length_squared == 0.0 {
+        0.0
+    } else {
+        ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0)
+    };
+    (start + segment * ratio - point).length()
+}
+
+pub fn reflect_2d(v: Vec2, n: Vec2) -> Vec2 {
+    let d = v.x * n.x + v.y * n.y; //dot(v, n)
+    Vec2::new(v.x - 2.0 * d * n.x, v.y - 2.0 * d * n.y)
+}
+
+/// Reflect the direction `v` of a ball moving at `speed` on a surface (of normal `n`) that moves at
+/// `surface_velocity`: the ball bounces like on a static surface, in the frame of the surface.
+/// The speed of the ball doesn't change (see `SpeedCurve`), only its direction.
+pub fn reflect_on_moving(v: Vec2, speed: f32, n: Vec2, surface_velocity: Vec2) -> Vec2 {
+    let velocity = v * speed;
+    let approach = (velocity - surface_velocity).dot(n);
+    // the surface runs away faster than the ball (or doesn't move): a plain reflection
+    if approach >= 0.0 || surface_velocity == Vec2::zero() {
+        return reflect_2d(v, n);
+    }
+    let reflected = velocity - n * (2.0 * approach);
+    if reflected.length_squared() > 0.0 {
+        reflected.normalize()
+    } else {
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ball::BallId;
+    use crate::paddle::PaddleRole;
+
+    #[test]
+    fn test_positive_angle() {
+        assert_eq!(positive_angle(0.0), 0.0);
+        assert_eq!(positive_angle(2.0 * PI), 0.0);
+        assert_eq!(positive_angle(PI), PI);
+        assert_eq!(positive_angle(-PI), PI);
+        assert_eq!(positive_angle(-0.5 * PI), 1.5 * PI);
+    }
+
+    fn new_ball() -> Ball {
+        Ball::new(BallId(0), Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0))
+    }
+
+    #[test]
+    fn test_collision_just_past_the_seam() {
+        let ball = new_ball();
+        let mut paddle = Paddle::new(PaddleRole::Inner, 100.0, 4.0, PI / 6.0);
+        paddle.set_angle(6.2);
+        let previous = Vec2::new(80.0 * 0.05f32.cos(), 80.0 * 0.05f32.sin());
+        let current = Vec2::new(110.0 * 0.05f32.cos(), 110.0 * 0.05f32.sin());
+        assert!(find_ball_paddle_collision_point(&current, &previous, &ball, &paddle).is_some());
+        paddle.set_angle(PI);
+        assert!(find_ball_paddle_collision_point(&current, &previous, &ball, &paddle).is_none());
+    }
+
+    fn polar(radius: f32, angle: f32) -> Vec2 {
+        Vec2::new(angle.cos(), angle.sin()) * radius
+    }
+
+    #[test]
+    fn test_collision_on_the_band() {
+        let ball = new_ball();
+        let paddle = Paddle::new(PaddleRole::Inner, 100.0, 4.0, PI / 6.0);
+        let previous = polar(80.0, 0.1);
+        let current = polar(110.0, 0.1);
+        let collision =
+            find_ball_paddle_collision_point(&current, &previous, &ball, &paddle).unwrap();
+        assert!((collision.point.length() - 93.0).abs() < 1e-3);
+        assert!((collision.ratio - 13.0 / 30.0).abs() < 1e-3);
+        assert!((collision.normal - polar(-1.0, 0.1)).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_collision_grazing_the_end_cap() {
+        let ball = new_ball();
+        let paddle = Paddle::new(PaddleRole::Inner, 100.0, 4.0, PI / 6.0);
+        // just after the end of the arc, the band is missed but not the end cap
+        let angle = PI / 12.0 + 0.03;
+        let previous = polar(80.0, angle);
+        let current = polar(110.0, angle);
+        let collision =
+            find_ball_paddle_collision_point(&current, &previous, &ball, &paddle).unwrap();
+        // the end cap pushes the ball toward the outside of the arc
+        assert!(collision.normal.dot(polar(1.0, angle + 0.5 * PI)) > 0.0);
+        let angle = PI / 12.0 + 0.1;
+        let previous = polar(80.0, angle);
+        let current = polar(110.0, angle);
+        assert!(find_ball_paddle_collision_point(&current, &previous, &ball, &paddle).is_none());
+    }
+
+    #[test]
+    fn test_collision_on_the_side() {
+        let ball = new_ball();
+        let paddle = Paddle::new(PaddleRole::Inner, 100.0, 4.0, PI / 6.0);
+        // the ball moves along the arc toward the end of the paddle
+        let previous = polar(100.0, PI / 12.0 + 0.2);
+        let current = polar(100.0, PI / 12.0);
+        let collision =
+            find_ball_paddle_collision_point(&current, &previous, &ball, &paddle).unwrap();
+        let tangent = polar(1.0, PI / 12.0 + 0.5 * PI);
+        assert!(collision.normal.dot(tangent) > 0.99);
+        let reflected = reflect_2d((current - previous).normalize(), collision.normal);
+        assert!(reflected.dot(tangent) > 0.9);
+    }
+
+    #[test]
+    fn test_reflect_on_moving_surface() {
+        let direction = Vec2::new(0.6, 0.8);
+        let wall = Vec2::new(-1.0, 0.0);
+        assert_eq!(
+            reflect_on_moving(direction, 100.0, wall, Vec2::zero()),
+            reflect_2d(direction, wall)
+        );
+        // the wall comes toward the ball: the ball goes back faster along the normal
+        let reflected = reflect_on_moving(direction, 100.0, wall, Vec2::new(-60.0, 0.0));
+        assert!((reflected - Vec2::new(-180.0, 80.0).normalize()).length() < 1e-5);
+        // the wall runs away faster than the ball
+        let reflected = reflect_on_moving(direction, 100.0, wall, Vec2::new(100.0, 0.0));
+        assert_eq!(reflected, reflect_2d(direction, wall));
+    }
+}
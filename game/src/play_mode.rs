@@ -0,0 +1,59 @@
+use crate::paddle::PaddleRole;
+use serde::{Deserialize, Serialize};
+
+/// Number of points to win a round in `PlayMode::Versus` (see `WinConditions`).
+pub const VERSUS_POINTS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PlayMode {
+    /// one player drives every paddle
+    #[default]
+    Solo,
+    /// one player per paddle, the score (impacts) is shared
+    Coop,
+    /// one player per paddle, a ball escaping through your ring is a point for the opponent
+    Versus,
+}
+
+impl PlayMode {
+    pub fn players(&self) -> usize {
+        match self {
+            PlayMode::Solo => 1,
+            PlayMode::Coop | PlayMode::Versus => 2,
+        }
+    }
+
+    pub fn next(&self) -> PlayMode {
+        match self {
+            PlayMode::Solo => PlayMode::Coop,
+            PlayMode::Coop => PlayMode::Versus,
+            PlayMode::Versus => PlayMode::Solo,
+        }
+    }
+}
+
+/// Points of each ring owner in `PlayMode::Versus`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersusScore {
+    pub outer: usize,
+    pub inner: usize,
+}
+
+impl VersusScore {
+    pub fn add_point(&mut self, role: PaddleRole) {
+        match role {
+            PaddleRole::Outer => self.outer += 1,
+            PaddleRole::Inner => self.inner += 1,
+        }
+    }
+
+    pub fn winner(&self, points_to_win: usize) -> Option<PaddleRole> {
+        if self.outer >= points_to_win {
+            Some(PaddleRole::Outer)
+        } else if self.inner >= points_to_win {
+            Some(PaddleRole::Inner)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Bonus / malus zones spawned inside the ring, collected by the balls passing through them.
+//!
+//! A collected zone gives a timed `Effect`, the `Simulation` applies the active effects to its
+//! `Scoreboard`, `Ball`s and `Paddle`s after every step.
+
+use crate::ball::Ball;
+use crate::paddle::PaddleRole;
+use crate::physics::distance_to_segment;
+use crate::rng::Rng;
+use crate::simulation::{RADIUS_EXTERN, RADIUS_INTERN};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    /// every hit scores 2 points
+    DoubleScore,
+    SlowBall,
+    /// the paddle follows the balls by itself
+    AutoPaddle,
+    /// malus
+    FastBall,
+    /// the paddle catches the balls, the player releases them
+    Magnetic,
+    /// an extra ball splits from the ball (no timed effect), only in multiball mode
+    Multiball,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 6] = [
+        PowerUpKind::DoubleScore,
+        PowerUpKind::SlowBall,
+        PowerUpKind::AutoPaddle,
+        PowerUpKind::FastBall,
+        PowerUpKind::Magnetic,
+        PowerUpKind::Multiball,
+    ];
+
+    pub fn is_malus(&self) -> bool {
+        matches!(self, PowerUpKind::FastBall)
+    }
+
+    /// The effect only applies to the paddle that sent the ball into the zone.
+    pub fn targets_paddle(&self) -> bool {
+        matches!(self, PowerUpKind::AutoPaddle | PowerUpKind::Magnetic)
+    }
+
+    /// Applied once when collected, instead of a timed `Effect`.
+    pub fn is_instant(&self) -> bool {
+        matches!(self, PowerUpKind::Multiball)
+    }
+
+    /// Factor applied to the velocity of the balls.
+    pub fn speed_factor(&self) -> f32 {
+        match self {
+            PowerUpKind::SlowBall => 0.6,
+            PowerUpKind::FastBall => 1.4,
+            _ => 1.0,
+        }
+    }
+}
+
+impl fmt::Display for PowerUpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PowerUpKind::DoubleScore => "double score",
+            PowerUpKind::SlowBall => "slow ball",
+            PowerUpKind::AutoPaddle => "auto-paddle",
+            PowerUpKind::FastBall => "fast ball",
+            PowerUpKind::Magnetic => "magnetic paddle",
+            PowerUpKind::Multiball => "multiball",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ZoneId(pub u32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub id: ZoneId,
+    pub kind: PowerUpKind,
+    pub position: Vec2,
+    pub radius: f32,
+    /// the zone disappears if it's not collected in time
+    pub remaining_ticks: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Effect {
+    pub kind: PowerUpKind,
+    pub remaining_ticks: u64,
+    /// the paddle targeted by the effect, `None` for every paddle (or if it doesn't target a paddle)
+    pub role: Option<PaddleRole>,
+}
+
+pub struct PowerUps {
+    /// without, no zone is spawned (the historical game)
+    pub enabled: bool,
+    /// spawn `Multiball` zones too (see `Multiball`)
+    pub multiball: bool,
+    /// a zone is spawned every `spawn_every_ticks` (if there is less than `max_zones`)
+    pub spawn_every_ticks: u64,
+    pub max_zones: usize,
+    pub zone_radius: f32,
+    /// the zones spawn between `(inner radius, outer radius)`
+    pub rings: (f32, f32),
+    /// lifetime of a zone not collected
+    pub zone_ticks: u64,
+    /// duration of an effect
+    pub effect_ticks: u64,
+    /// a magnetic paddle releases the ball by itself after `hold_ticks`
+    pub hold_ticks: u64,
+    pub zones: Vec<Zone>,
+    pub effects: Vec<Effect>,
+    rng: Rng,
+    next_zone_id: u32,
+}
+
+impl Default for PowerUps {
+    fn default() -> Self {
+        // durations at 120 steps per second (see `FixedTimestep`)
+        PowerUps {
+            enabled: false,
+            multiball: false,
+            spawn_every_ticks: 120 * 6,
+            max_zones: 2,
+            zone_radius: 14.0,
+            rings: (RADIUS_INTERN, RADIUS_EXTERN),
+            zone_ticks: 120 * 10,
+            effect_ticks: 120 * 8,
+            hold_ticks: 120 * 2,
+            zones: vec![],
+            effects: vec![],
+            rng: Rng::new(0),
+            next_zone_id: 0,
+        }
+    }
+}
+
+impl PowerUps {
+    /// Remove the zones and the effects of the previous round.
+    pub fn reset(&mut self, seed: u64) {
+        self.zones.clear();
+        self.effects.clear();
+        // not the rng of the simulation, so the serves don't depend on the power-ups
+        self.rng = Rng::new(!seed);
+    }
+
+    /// Advance the timers by one step (the `tick` of the simulation), spawn a zone when it's time.
+    pub fn tick(&mut self, tick: u64) {
+        for zone in self.zones.iter_mut() {
+            zone.remaining_ticks = zone.remaining_ticks.saturating_sub(1);
+        }
+        self.zones.retain(|zone| zone.remaining_ticks > 0);
+        for effect in self.effects.iter_mut() {
+            effect.remaining_ticks = effect.remaining_ticks.saturating_sub(1);
+        }
+        self.effects.retain(|effect| effect.remaining_ticks > 0);
+        if self.enabled
+            && tick.is_multiple_of(self.spawn_every_ticks)
+            && self.zones.len() < self.max_zones
+        {
+            self.spawn();
+        }
+    }
+
+    /// Spawn a zone at a random place between the paddles.
+    pub fn spawn(&mut self) -> ZoneId {
+        // without multiball, the same draws as before the `Multiball` zones
+        let kinds = if self.multiball {
+            &PowerUpKind::ALL[..]
+        } else {
+            &PowerUpKind::ALL[..PowerUpKind::ALL.len() - 1]
+        };
+        let kind = kinds[(self.rng.next_u64() % kinds.len() as u64) as usize];
+        // keep some room for the paddles
+        let margin = self.zone_radius + 20.0;
+        let distance = self.rng.range(self.rings.0 + margin, self.rings.1 - margin);
+        let angle = self.rng.range(0.0, 2.0 * PI);
+        let id = ZoneId(self.next_zone_id);
+        self.next_zone_id += 1;
+        self.zones.push(Zone {
+            id,
+            kind,
+            position: Vec2::new(angle.cos(), angle.sin()) * distance,
+            radius: self.zone_radius,
+            remaining_ticks: self.zone_ticks,
+        });
+        id
+    }
+
+    /// Collect the zones crossed by the `ball` during its last move, return the kinds collected.
+    pub fn collect(&mut self, ball: &Ball) -> Vec<PowerUpKind> {
+        let mut collected = vec![];
+        self.zones.retain(|zone| {
+            let distance =
+                distance_to_segment(zone.position, ball.position_previous, ball.position);
+            if distance <= zone.radius + ball.radius {
+                collected.push(zone.kind);
+                false
+            } else {
+                true
+            }
+        });
+        for kind in collected.iter().filter(|kind| !kind.is_instant()) {
+            self.activate(*kind, ball.last_hit);
+        }
+        collected
+    }
+
+    /// Start the effect of `kind` for the paddle `role` (the last paddle to hit the ball), or
+    /// restart it if it's already active.
+    pub fn activate(&mut self, kind: PowerUpKind, role: Option<PaddleRole>) {
+        let role = if kind.targets_paddle() { role } else { None };
+        let effect_ticks = self.effect_ticks;
+        match self
+            .effects
+            .iter_mut()
+            .find(|effect| effect.kind == kind && effect.role == role)
+        {
+            Some(effect) => effect.remaining_ticks = effect_ticks,
+            None => self.effects.push(Effect {
+                kind,
+                remaining_ticks: effect_ticks,
+                role,
+            }),
+        }
+    }
+
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// Is the effect of `kind` active for the paddle `role`.
+    pub fn is_active_for(&self, kind: PowerUpKind, role: PaddleRole) -> bool {
+        self.effects
+            .iter()
+            .any(|effect| effect.kind == kind && effect.role.is_none_or(|r| r == role))
+    }
+
+    /// Factor of the velocity of every ball.
+    pub fn speed_factor(&self) -> f32 {
+        self.effects
+            .iter()
+            .map(|effect| effect.kind.speed_factor())
+            .product()
+    }
+
+    /// Points scored by a hit.
+    pub fn score_multiplier(&self) -> usize {
+        if self.is_active(PowerUpKind::DoubleScore) {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ball_moving(from: Vec2, to: Vec2) -> Ball {
+        let mut ball = Ball::moving(from, to);
+        ball.last_hit = Some(PaddleRole::Outer);
+        ball
+    }
+
+    #[test]
+    fn test_zones_spawn_inside_the_ring() {
+        let mut power_ups = PowerUps {
+            enabled: true,
+            max_zones: 100,
+            ..PowerUps::default()
+        };
+        power_ups.reset(7);
+        for tick in 1..=(power_ups.spawn_every_ticks * 50) {
+            power_ups.tick(tick);
+        }
+        assert!(!power_ups.zones.is_empty());
+        for zone in power_ups.zones.iter() {
+            let distance = zone.position.length();
+            assert!(distance - zone.radius > RADIUS_INTERN);
+            assert!(distance + zone.radius < RADIUS_EXTERN);
+        }
+        // the same seed gives the same zones
+        let mut other = PowerUps {
+            enabled: true,
+            max_zones: 100,
+            ..PowerUps::default()
+        };
+        other.reset(7);
+        for tick in 1..=(other.spawn_every_ticks * 50) {
+            other.tick(tick);
+        }
+        assert_eq!(other.zones, power_ups.zones);
+    }
+
+    #[test]
+    fn test_collect_and_expire() {
+        let mut power_ups = PowerUps::default();
+        power_ups.zones.push(Zone {
+            id: ZoneId(0),
+            kind: PowerUpKind::AutoPaddle,
+            position: Vec2::new(200.0, 10.0),
+            radius: 10.0,
+            remaining_ticks: 100,
+        });
+        // the ball passes near the zone without touching it
+        let ball = ball_moving(Vec2::new(150.0, 30.0), Vec2::new(250.0, 30.0));
+        assert!(power_ups.collect(&ball).is_empty());
+        // the ball passes through the zone during the step
+        let ball = ball_moving(Vec2::new(150.0, 0.0), Vec2::new(250.0, 0.0));
+        assert_eq!(power_ups.collect(&ball), vec![PowerUpKind::AutoPaddle]);
+        assert!(power_ups.zones.is_empty());
+        assert!(power_ups.is_active_for(PowerUpKind::AutoPaddle, PaddleRole::Outer));
+        assert!(!power_ups.is_active_for(PowerUpKind::AutoPaddle, PaddleRole::Inner));
+        for tick in 1..power_ups.effect_ticks {
+            power_ups.tick(tick);
+        }
+        assert!(power_ups.is_active(PowerUpKind::AutoPaddle));
+        // collected again, the effect restarts
+        power_ups.activate(PowerUpKind::AutoPaddle, Some(PaddleRole::Outer));
+        assert_eq!(power_ups.effects.len(), 1);
+        for tick in 0..power_ups.effect_ticks {
+            power_ups.tick(tick);
+        }
+        assert!(!power_ups.is_active(PowerUpKind::AutoPaddle));
+    }
+
+    #[test]
+    fn test_effects_combine() {
+        let mut power_ups = PowerUps::default();
+        assert_eq!(power_ups.speed_factor(), 1.0);
+        assert_eq!(power_ups.score_multiplier(), 1);
+        power_ups.activate(PowerUpKind::SlowBall, Some(PaddleRole::Inner));
+        power_ups.activate(PowerUpKind::DoubleScore, None);
+        assert_eq!(power_ups.speed_factor(), 0.6);
+        assert_eq!(power_ups.score_multiplier(), 2);
+        // the speed effects target every ball
+        assert_eq!(power_ups.effects[0].role, None);
+        power_ups.activate(PowerUpKind::FastBall, None);
+        assert!((power_ups.speed_factor() - 0.84).abs() < 1e-5);
+    }
+}
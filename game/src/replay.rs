@@ -0,0 +1,413 @@
+//! Record the inputs of a round to replay it later, step by step.
+//!
+//! A replay file is a text file:
+//!
+//! ```text
+//! ld47_keep_inside replay 3
+//! {"seed":1601676000,"difficulty":"Normal","play_mode":"Solo","step_seconds":0.008333334,"paddles":[4.2,4.2],"ticks":2713,"power_ups":false,"level":{"name":"default",...},"multiball":{...}}
+//! 1 4.25 4.25
+//! 3 4.3 -
+//! ...
+//! ```
+//!
+//! The first line is the version header, the second the configuration of the round (json),
+//! then one line per step with inputs: the tick, the outer angle and the inner angle (`-` to keep the angle),
+//! optionally followed by the paddles releasing their balls (`o` for the outer, `i` for the inner, `oi` for both).
+//! Steps without inputs are omitted.
+
+use crate::ball::Ball;
+use crate::controller::{controlled_inputs, PaddleController};
+use crate::level::Level;
+use crate::multiball::Multiball;
+use crate::paddle::{Paddle, PaddleRole};
+use crate::play_mode::PlayMode;
+use crate::simulation::{Event, Inputs, Simulation};
+use crate::speed_curve::Difficulty;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{BufRead, Write};
+
+/// Version of the replay format (and of the simulation), a replay is only valid for the same version.
+pub const REPLAY_VERSION: u32 = 3;
+const REPLAY_MAGIC: &str = "ld47_keep_inside replay";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub play_mode: PlayMode,
+    /// duration of every step (see `FixedTimestep`)
+    pub step_seconds: f32,
+    /// `angle_origin` of each paddle when the round starts (paddles can move during the countdown)
+    pub paddles: Vec<f32>,
+    /// number of steps of the round
+    pub ticks: u64,
+    /// with power-up zones
+    pub power_ups: bool,
+    /// the arena of the round
+    pub level: Level,
+    pub multiball: Multiball,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub config: ReplayConfig,
+    /// inputs by tick (the tick of the `Simulation` after the step), sorted by tick
+    pub inputs: Vec<(u64, Inputs)>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    /// not a replay, or a replay of an other version
+    Header(String),
+    Config(serde_json::Error),
+    Inputs {
+        line: usize,
+        msg: String,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "failed to read / write the replay: {}", err),
+            ReplayError::Header(msg) => write!(f, "invalid replay header: {}", msg),
+            ReplayError::Config(err) => write!(f, "invalid replay config: {}", err),
+            ReplayError::Inputs { line, msg } => {
+                write!(f, "invalid replay inputs at line {}: {}", line, msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl Replay {
+    /// Start to record the round of `sim` (call it before the first step of the round).
+    pub fn record_from(sim: &Simulation, difficulty: Difficulty, step_seconds: f32) -> Replay {
+        Replay {
+            config: ReplayConfig {
+                seed: sim.seed,
+                difficulty,
+                play_mode: sim.play_mode,
+                step_seconds,
+                paddles: sim
+                    .paddles
+                    .iter()
+                    .map(|paddle| paddle.angle_origin)
+                    .collect(),
+                ticks: sim.tick,
+                power_ups: sim.power_ups.enabled,
+                level: Level::of(sim),
+                multiball: sim.multiball.clone(),
+            },
+            inputs: vec![],
+        }
+    }
+
+    /// Record the `inputs` of the step that advanced the simulation to `tick`.
+    pub fn record(&mut self, tick: u64, inputs: &Inputs) {
+        self.config.ticks = tick;
+        if *inputs != Inputs::default() {
+            self.inputs.push((tick, inputs.clone()));
+        }
+    }
+
+    /// A `Simulation` ready to play the first step of the replay.
+    pub fn simulation(&self) -> Simulation {
+        let mut sim = Simulation::new();
+        sim.speed_curve = self.config.difficulty.speed_curve();
+        sim.play_mode = self.config.play_mode;
+        sim.seed = self.config.seed;
+        sim.power_ups.enabled = self.config.power_ups;
+        self.config.level.apply(&mut sim, self.config.difficulty);
+        sim.multiball = self.config.multiball.clone();
+        sim.start();
+        for (paddle, angle_origin) in sim.paddles.iter_mut().zip(self.config.paddles.iter()) {
+            paddle.angle_origin = *angle_origin;
+        }
+        sim
+    }
+
+    /// Play the whole replay, return the simulation at the end of the round and the events.
+    pub fn play(&self) -> (Simulation, Vec<Event>) {
+        let mut sim = self.simulation();
+        let mut player = ReplayPlayer::new(self.clone());
+        let mut events = vec![];
+        while sim.tick < self.config.ticks {
+            let inputs = controlled_inputs(&mut player, &sim);
+            events.extend(sim.step(self.config.step_seconds, &inputs));
+        }
+        (sim, events)
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), ReplayError> {
+        writeln!(out, "{} {}", REPLAY_MAGIC, REPLAY_VERSION)?;
+        let config = serde_json::to_string(&self.config).map_err(ReplayError::Config)?;
+        writeln!(out, "{}", config)?;
+        let angle = |angle: Option<f32>| match angle {
+            // `Display` of f32 is the shortest representation that reads back to the same value
+            Some(angle) => format!("{}", angle),
+            None => "-".to_string(),
+        };
+        for (tick, inputs) in self.inputs.iter() {
+            write!(
+                out,
+                "{} {} {}",
+                tick,
+                angle(inputs.outer_angle),
+                angle(inputs.inner_angle)
+            )?;
+            if inputs.outer_release || inputs.inner_release {
+                write!(out, " ")?;
+                if inputs.outer_release {
+                    write!(out, "o")?;
+                }
+                if inputs.inner_release {
+                    write!(out, "i")?;
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: BufRead>(input: R) -> Result<Replay, ReplayError> {
+        let mut lines = input.lines();
+        let header = lines
+            .next()
+            .transpose()?
+            .ok_or_else(|| ReplayError::Header("empty file".to_string()))?;
+        let version = header
+            .strip_prefix(REPLAY_MAGIC)
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| ReplayError::Header(format!("not a replay: '{}'", header)))?;
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::Header(format!(
+                "version {} is not supported (expected {})",
+                version, REPLAY_VERSION
+            )));
+        }
+        let config = lines
+            .next()
+            .transpose()?
+            .ok_or_else(|| ReplayError::Header("no config".to_string()))?;
+        let config: ReplayConfig = serde_json::from_str(&config).map_err(ReplayError::Config)?;
+        let mut inputs: Vec<(u64, Inputs)> = vec![];
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let error = |msg: String| ReplayError::Inputs {
+                line: index + 3,
+                msg,
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 && fields.len() != 4 {
+                return Err(error(format!(
+                    "expected 3 or 4 fields, found {}",
+                    fields.len()
+                )));
+            }
+            let tick = fields[0]
+                .parse::<u64>()
+                .map_err(|err| error(format!("tick '{}': {}", fields[0], err)))?;
+            if inputs.last().is_some_and(|(previous, _)| *previous >= tick) {
+                return Err(error(format!(
+                    "tick {} is not after the previous one",
+                    tick
+                )));
+            }
+            let mut step_inputs = Inputs::default();
+            for (role, field) in [PaddleRole::Outer, PaddleRole::Inner]
+                .iter()
+                .zip(fields[1..3].iter())
+            {
+                if *field != "-" {
+                    let angle = field
+                        .parse::<f32>()
+                        .map_err(|err| error(format!("angle '{}': {}", field, err)))?;
+                    step_inputs.set_angle(*role, angle);
+                }
+            }
+            if let Some(release) = fields.get(3) {
+                for c in release.chars() {
+                    match c {
+                        'o' => step_inputs.set_release(PaddleRole::Outer),
+                        'i' => step_inputs.set_release(PaddleRole::Inner),
+                        _ => {
+                            return Err(error(format!(
+                                "release '{}': expected o, i or oi",
+                                release
+                            )))
+                        }
+                    }
+                }
+            }
+            inputs.push((tick, step_inputs));
+        }
+        Ok(Replay { config, inputs })
+    }
+}
+
+/// Provide the recorded inputs, step after step.
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    next: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer { replay, next: 0 }
+    }
+
+    /// Restart from the first step.
+    pub fn rewind(&mut self) {
+        self.next = 0;
+    }
+
+    /// The inputs of the step that advances the simulation to `tick` (ticks should be requested in order).
+    pub fn inputs_for(&mut self, tick: u64) -> Inputs {
+        while let Some((recorded_tick, inputs)) = self.replay.inputs.get(self.next) {
+            match recorded_tick.cmp(&tick) {
+                Ordering::Less => self.next += 1,
+                Ordering::Equal => return inputs.clone(),
+                Ordering::Greater => break,
+            }
+        }
+        Inputs::default()
+    }
+
+    pub fn is_finished(&self, tick: u64) -> bool {
+        tick >= self.replay.config.ticks
+    }
+}
+
+impl PaddleController for ReplayPlayer {
+    fn target_angle(&mut self, paddle: &Paddle, _balls: &[Ball], tick: u64) -> Option<f32> {
+        self.inputs_for(tick + 1).angle_for(paddle.role)
+    }
+
+    fn release(&mut self, paddle: &Paddle, tick: u64) -> bool {
+        self.inputs_for(tick + 1).release_for(paddle.role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::ControlMode;
+
+    /// Record a round where the paddles follow the ball, with some lag, until game over.
+    fn record_round(seed: u64) -> (Replay, usize) {
+        let mut sim = Simulation::new();
+        sim.seed = seed;
+        sim.start();
+        sim.apply_inputs(&ControlMode::default().dispatch(Some(1.0), None));
+        let step_seconds = 1.0 / 120.0;
+        let mut replay = Replay::record_from(&sim, Difficulty::Normal, step_seconds);
+        let mut game_over = false;
+        while !game_over && sim.tick < 20_000 {
+            let inputs = if sim.tick % 3 == 1 {
+                ControlMode::default().dispatch(
+                    sim.balls
+                        .first()
+                        .map(|ball| ball.position.y.atan2(ball.position.x) + 0.1),
+                    None,
+                )
+            } else {
+                Inputs::default()
+            };
+            game_over = sim
+                .step(step_seconds, &inputs)
+                .iter()
+                .any(|e| matches!(e, Event::GameOver));
+            replay.record(sim.tick, &inputs);
+        }
+        (replay, sim.scoreboard.score)
+    }
+
+    #[test]
+    fn test_replay_reproduces_the_score() {
+        let (replay, score) = record_round(42);
+        assert!(score > 0);
+        let mut file = vec![];
+        replay.write(&mut file).unwrap();
+        let read = Replay::read(file.as_slice()).unwrap();
+        assert_eq!(read, replay);
+        let (sim, events) = read.play();
+        assert_eq!(sim.scoreboard.score, score);
+        assert!(matches!(events.last(), Some(Event::GameOver)));
+    }
+
+    #[test]
+    fn test_release_roundtrip() {
+        let mut replay = Replay::record_from(&Simulation::new(), Difficulty::Normal, 1.0 / 120.0);
+        let mut inputs = Inputs::default();
+        inputs.set_release(PaddleRole::Inner);
+        replay.record(3, &inputs);
+        inputs.set_angle(PaddleRole::Outer, 1.5);
+        inputs.set_release(PaddleRole::Outer);
+        replay.record(4, &inputs);
+        let mut file = vec![];
+        replay.write(&mut file).unwrap();
+        assert!(String::from_utf8_lossy(&file).ends_with("3 - - i\n4 1.5 - oi\n"));
+        assert_eq!(Replay::read(file.as_slice()).unwrap(), replay);
+        file.extend(b"5 - - x\n");
+        assert!(matches!(
+            Replay::read(file.as_slice()),
+            Err(ReplayError::Inputs { line: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn test_older_version_is_rejected() {
+        let (replay, _) = record_round(3);
+        let mut file = vec![];
+        replay.write(&mut file).unwrap();
+        let file = String::from_utf8(file).unwrap();
+        // the same round, recorded by an older simulation
+        let older = file.replacen(
+            &format!("{} {}", REPLAY_MAGIC, REPLAY_VERSION),
+            &format!("{} {}", REPLAY_MAGIC, REPLAY_VERSION - 1),
+            1,
+        );
+        match Replay::read(older.as_bytes()) {
+            Err(ReplayError::Header(msg)) => assert!(msg.contains("not supported")),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(matches!(
+            Replay::read("".as_bytes()),
+            Err(ReplayError::Header(_))
+        ));
+        assert!(matches!(
+            Replay::read("ld47_keep_inside replay 999\n{}".as_bytes()),
+            Err(ReplayError::Header(_))
+        ));
+        assert!(matches!(
+            Replay::read(format!("{} {}\n{{", REPLAY_MAGIC, REPLAY_VERSION).as_bytes()),
+            Err(ReplayError::Config(_))
+        ));
+        let (replay, _) = record_round(1);
+        let mut file = vec![];
+        replay.write(&mut file).unwrap();
+        file.extend(b"12 1.0\n");
+        match Replay::read(file.as_slice()) {
+            Err(ReplayError::Inputs { line, .. }) => assert_eq!(line, replay.inputs.len() + 3),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,1458 @@
+use crate::angular_interval::angle_delta;
+use crate::arena::ArenaMotion;
+use crate::ball::{Attachment, Ball, BallId};
+use crate::level::{Spawn, WinConditions};
+use crate::multiball::{collide_balls, split_direction, Multiball};
+use crate::obstacle::Obstacle;
+use crate::paddle::{Paddle, PaddleRole};
+use crate::physics::{find_ball_paddle_collision_point, reflect_on_moving};
+use crate::play_mode::{PlayMode, VersusScore};
+use crate::power_up::{PowerUpKind, PowerUps};
+use crate::rng::Rng;
+use crate::speed_curve::SpeedCurve;
+use crate::spin::Spin;
+use crate::teleporter::Teleporter;
+use glam::Vec2;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::f32::consts::{FRAC_PI_6, PI};
+
+pub const RADIUS_EXTERN: f32 = 285.0;
+pub const RADIUS_INTERN: f32 = 108.0;
+/// Impacts handled for a ball during a step, a fast ball can bounce on several rings (and
+/// obstacles) in a step.
+const MAX_IMPACTS_PER_STEP: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct Scoreboard {
+    pub score: usize,
+    pub best: usize,
+    pub versus: VersusScore,
+    /// points scored by a hit (see `PowerUps`)
+    pub multiplier: usize,
+    /// the round ended on a win condition (see `WinConditions`)
+    pub won: bool,
+}
+
+impl Default for Scoreboard {
+    fn default() -> Self {
+        Scoreboard {
+            score: 0,
+            best: 0,
+            versus: VersusScore::default(),
+            multiplier: 1,
+            won: false,
+        }
+    }
+}
+
+/// What the player(s) requested since the previous step.
+///
+/// Angles are in radian, `None` to keep the current angle of the paddle.
+/// A release frees the balls caught by the paddle (when it's magnetic).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Inputs {
+    pub outer_angle: Option<f32>,
+    pub inner_angle: Option<f32>,
+    pub outer_release: bool,
+    pub inner_release: bool,
+}
+
+impl Inputs {
+    pub fn angle_for(&self, role: PaddleRole) -> Option<f32> {
+        match role {
+            PaddleRole::Outer => self.outer_angle,
+            PaddleRole::Inner => self.inner_angle,
+        }
+    }
+
+    pub fn set_angle(&mut self, role: PaddleRole, angle: f32) {
+        match role {
+            PaddleRole::Outer => self.outer_angle = Some(angle),
+            PaddleRole::Inner => self.inner_angle = Some(angle),
+        }
+    }
+
+    pub fn release_for(&self, role: PaddleRole) -> bool {
+        match role {
+            PaddleRole::Outer => self.outer_release,
+            PaddleRole::Inner => self.inner_release,
+        }
+    }
+
+    pub fn set_release(&mut self, role: PaddleRole) {
+        match role {
+            PaddleRole::Outer => self.outer_release = true,
+            PaddleRole::Inner => self.inner_release = true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Escape {
+    /// the ball went beyond the outer ring
+    Outside,
+    /// the ball went under the inner ring (toward the center)
+    Inside,
+}
+
+impl Escape {
+    /// The role of the paddle that should have caught the ball.
+    pub fn missed_by(&self) -> PaddleRole {
+        match self {
+            Escape::Outside => PaddleRole::Outer,
+            Escape::Inside => PaddleRole::Inner,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Hit {
+        paddle: usize,
+        ball: BallId,
+        /// direction of the ball before the impact
+        direction: Vec2,
+        /// points scored by the hit (see `Paddle`)
+        points: usize,
+    },
+    /// the ball missed the paddles and is removed from the simulation
+    Escaped { ball: BallId, escape: Escape },
+    /// in versus, the owner of the `role` ring scores a point
+    Point { role: PaddleRole },
+    /// the ball passed through a power-up zone, its effect is active
+    Collected { ball: BallId, kind: PowerUpKind },
+    /// the ball bounced on the obstacle (index), the hit scores `points`
+    Bounced {
+        obstacle: usize,
+        ball: BallId,
+        points: usize,
+    },
+    /// the ball entered a pad of the teleporter (index) and came out of the other pad
+    Teleported { teleporter: usize, ball: BallId },
+    /// an extra ball (multiball) split from the ball `from`
+    Split { from: BallId, ball: BallId },
+    /// 2 balls bounced on each other
+    Collided { balls: [BallId; 2] },
+    /// the ball is caught by a magnetic paddle (after its `Hit`)
+    Caught { paddle: usize, ball: BallId },
+    /// the ball caught by the paddle is released
+    Released { paddle: usize, ball: BallId },
+    /// the round is over (last ball escaped, a win condition, or a winner in versus), the best score is already committed
+    GameOver,
+}
+
+pub struct Simulation {
+    pub balls: Vec<Ball>,
+    pub paddles: Vec<Paddle>,
+    pub scoreboard: Scoreboard,
+    pub speed_curve: SpeedCurve,
+    pub play_mode: PlayMode,
+    pub power_ups: PowerUps,
+    pub obstacles: Vec<Obstacle>,
+    pub teleporters: Vec<Teleporter>,
+    pub multiball: Multiball,
+    /// where the balls are served, with a normalized direction (see `Level`)
+    pub spawns: Vec<Spawn>,
+    pub win: WinConditions,
+    /// the arena can turn and pulse during the round
+    pub arena: ArenaMotion,
+    /// how the paddles give their spin to the balls
+    pub spin: Spin,
+    /// number of steps since the start of the round
+    pub tick: u64,
+    /// seed of the round, the same seed and the same inputs give the same round
+    pub seed: u64,
+    rng: Rng,
+    next_ball_id: u32,
+    /// the score that gives the next extra ball
+    next_milestone: Option<usize>,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation::new()
+    }
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Simulation {
+            balls: vec![],
+            paddles: vec![
+                Paddle::new(PaddleRole::Outer, RADIUS_EXTERN, 12.0, FRAC_PI_6),
+                Paddle::new(PaddleRole::Inner, RADIUS_INTERN, 4.0, FRAC_PI_6),
+            ],
+            scoreboard: Scoreboard::default(),
+            speed_curve: SpeedCurve::default(),
+            play_mode: PlayMode::default(),
+            power_ups: PowerUps::default(),
+            obstacles: vec![],
+            teleporters: vec![],
+            multiball: Multiball::default(),
+            spawns: vec![Spawn::default()],
+            win: WinConditions::default(),
+            arena: ArenaMotion::default(),
+            spin: Spin::default(),
+            tick: 0,
+            seed: 0,
+            rng: Rng::new(0),
+            next_ball_id: 0,
+            next_milestone: None,
+        }
+    }
+
+    /// Start a new round: commit the best score, reset the score and replace existing balls by a new one.
+    pub fn start(&mut self) {
+        self.commit_best();
+        self.scoreboard.score = 0;
+        self.scoreboard.versus = VersusScore::default();
+        self.scoreboard.won = false;
+        self.balls.clear();
+        self.tick = 0;
+        self.rng = Rng::new(self.seed);
+        for paddle in self.paddles.iter_mut() {
+            paddle.angular_velocity = 0.0;
+        }
+        self.arena.elapsed = 0.0;
+        if !self.arena.is_static() {
+            self.place_arena();
+        }
+        self.power_ups.multiball = self.multiball.enabled;
+        self.power_ups.rings = self.ring_bounds();
+        self.power_ups.reset(self.seed);
+        self.apply_effects();
+        self.next_milestone = self.multiball.next_milestone(0);
+        for obstacle in self.obstacles.iter_mut() {
+            obstacle.elapsed = 0.0;
+        }
+        self.serve();
+    }
+
+    /// Spawn the ball to (re)start the rally.
+    ///
+    /// The spawn (if there are several) and the direction of the serve are shuffled by the seed,
+    /// except with the seed `0` (the historical serve).
+    pub fn serve(&mut self) {
+        let spawn = match self.spawns.len() {
+            0 => Spawn::default(),
+            _ if self.seed == 0 => self.spawns[0].clone(),
+            n => self.spawns[(self.rng.next_u64() % n as u64) as usize].clone(),
+        };
+        let spread = if self.seed == 0 {
+            0.0
+        } else {
+            self.rng.range(-0.25, 0.25)
+        };
+        // the spawns turn with the arena
+        let rotation = self.arena.rotation();
+        let (position, spread) = if rotation == 0.0 {
+            (spawn.position, spread)
+        } else {
+            let (sin, cos) = rotation.sin_cos();
+            let position = spawn.position;
+            (
+                Vec2::new(
+                    position.x * cos - position.y * sin,
+                    position.x * sin + position.y * cos,
+                ),
+                spread + rotation,
+            )
+        };
+        let (sin, cos) = spread.sin_cos();
+        let direction = spawn.direction;
+        self.spawn_ball(
+            position,
+            Vec2::new(
+                direction.x * cos - direction.y * sin,
+                direction.x * sin + direction.y * cos,
+            ),
+        );
+    }
+
+    pub fn spawn_ball(&mut self, position: Vec2, mvt_dir: Vec2) -> BallId {
+        let id = BallId(self.next_ball_id);
+        self.next_ball_id += 1;
+        let mut ball = Ball::new(id, position, mvt_dir);
+        ball.speed_factor = self.power_ups.speed_factor();
+        self.balls.push(ball);
+        id
+    }
+
+    /// Spawn an extra ball from the ball `from` (or from an other ball in play if it's caught by a
+    /// paddle), both balls go apart. Each ball keeps its own `velocity_indicator`, the new one
+    /// starts like a served ball. Return the ids of the ball split and of the new ball.
+    pub fn split_ball(&mut self, from: BallId) -> Option<(BallId, BallId)> {
+        if self.balls.len() >= self.multiball.max_balls {
+            return None;
+        }
+        let source = self
+            .balls
+            .iter_mut()
+            .filter(|ball| ball.attached.is_none())
+            .min_by_key(|ball| ball.id != from)?;
+        let spread = 0.2 * PI;
+        let direction = source.mvt_dir;
+        let from = source.id;
+        source.mvt_dir = split_direction(direction, -spread / 2.0);
+        let position = source.position;
+        let last_hit = source.last_hit;
+        let id = self.spawn_ball(position, split_direction(direction, spread / 2.0));
+        if let Some(ball) = self.balls.last_mut() {
+            ball.last_hit = last_hit;
+        }
+        Some((from, id))
+    }
+
+    fn commit_best(&mut self) {
+        // in versus, the score is only the length of the rallies
+        if self.play_mode == PlayMode::Versus {
+            return;
+        }
+        self.scoreboard.best = self.scoreboard.best.max(self.scoreboard.score);
+    }
+
+    /// The outermost ring for `Outer`, the innermost ring for `Inner`: the balls escape beyond
+    /// the outermost ring and under the innermost ring.
+    fn boundary(&self, role: PaddleRole) -> Option<&Paddle> {
+        let by_radius = |a: &&Paddle, b: &&Paddle| {
+            a.radius_origin
+                .partial_cmp(&b.radius_origin)
+                .unwrap_or(Ordering::Equal)
+        };
+        match role {
+            PaddleRole::Outer => self.paddles.iter().max_by(by_radius),
+            PaddleRole::Inner => self.paddles.iter().min_by(by_radius),
+        }
+    }
+
+    /// The paddles that drive the inputs: the first paddle of each role, the other paddles of the
+    /// role follow it (with their `offset`).
+    pub fn driving_paddles(&self) -> impl Iterator<Item = &Paddle> {
+        self.paddles
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, paddle)| {
+                let first = self.paddles[..index]
+                    .iter()
+                    .all(|other| other.role != paddle.role);
+                if first {
+                    Some(paddle)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// The radius of the outermost (`Outer`) or the innermost (`Inner`) ring, the balls escape beyond the outer ring and under the inner ring.
+    pub fn ring_radius(&self, role: PaddleRole) -> f32 {
+        match self.boundary(role) {
+            Some(paddle) => paddle.radius_origin,
+            None => match role {
+                PaddleRole::Outer => RADIUS_EXTERN,
+                PaddleRole::Inner => RADIUS_INTERN,
+            },
+        }
+    }
+
+    /// The distance between the center and the ring of the paddle `role`, in the direction of
+    /// `point` (the rings may not be circles, see `Track`).
+    pub fn ring_radius_at(&self, role: PaddleRole, point: Vec2) -> f32 {
+        match self.boundary(role) {
+            Some(paddle) => paddle.radius_at(point),
+            None => self.ring_radius(role),
+        }
+    }
+
+    /// The room between the rings: `(farthest point of the inner ring, nearest point of the outer ring)`
+    /// from the center.
+    pub fn ring_bounds(&self) -> (f32, f32) {
+        let range = |role: PaddleRole| self.boundary(role).map(|paddle| paddle.radius_range());
+        (
+            range(PaddleRole::Inner).map_or(RADIUS_INTERN, |range| range.1),
+            range(PaddleRole::Outer).map_or(RADIUS_EXTERN, |range| range.0),
+        )
+    }
+
+    pub fn ball(&self, id: BallId) -> Option<&Ball> {
+        self.balls.iter().find(|ball| ball.id == id)
+    }
+
+    /// Move the paddles without advancing the simulation (eg during a countdown).
+    pub fn apply_inputs(&mut self, inputs: &Inputs) {
+        for paddle in self.paddles.iter_mut() {
+            if let Some(angle) = inputs.angle_for(paddle.role) {
+                paddle.set_angle(angle + paddle.offset);
+            }
+        }
+    }
+
+    /// Advance the simulation by `delta_seconds` and return what happened during the step.
+    ///
+    /// To be reproducible, the simulation should always be advanced by the same `delta_seconds`
+    /// (see `FixedTimestep`).
+    pub fn step(&mut self, delta_seconds: f32, inputs: &Inputs) -> Vec<Event> {
+        self.tick += 1;
+        let angles: Vec<f32> = self
+            .paddles
+            .iter()
+            .map(|paddle| paddle.angle_origin)
+            .collect();
+        self.apply_inputs(inputs);
+        self.follow_balls();
+        // the angular velocity of the paddles, measured at every step whatever drives them
+        for (paddle, angle) in self.paddles.iter_mut().zip(angles) {
+            let measured = angle_delta(angle, paddle.angle_origin) / delta_seconds;
+            paddle.angular_velocity =
+                self.spin
+                    .filter(paddle.angular_velocity, measured, delta_seconds);
+        }
+        for obstacle in self.obstacles.iter_mut() {
+            obstacle.advance(delta_seconds);
+        }
+        // how the rings moved during the step (see `Paddle::follow_ring`)
+        let (rotation, scale) = (self.arena.rotation(), self.arena.scale());
+        self.arena.advance(delta_seconds);
+        if !self.arena.is_static() {
+            self.place_arena();
+        }
+        let ring_motion = (self.arena.rotation() - rotation, self.arena.scale() / scale);
+        let mut events = vec![];
+        let mut splits = vec![];
+        for ball in self.balls.iter_mut() {
+            let speed = ball.velocity(&self.speed_curve);
+            if let Some(attachment) = ball.attached.as_mut() {
+                let paddle = &self.paddles[attachment.paddle];
+                attachment.held_ticks += 1;
+                let angle = paddle.angle_origin + attachment.angle_offset;
+                ball.position_previous = ball.position;
+                ball.position = paddle.carry(angle, attachment.distance);
+                if inputs.release_for(paddle.role)
+                    || !paddle.magnetic
+                    || attachment.held_ticks >= self.power_ups.hold_ticks
+                {
+                    let normal_angle = paddle.angle_origin + attachment.normal_offset;
+                    let normal = Vec2::new(normal_angle.cos(), normal_angle.sin());
+                    // thrown in the direction of the rotation of the paddle
+                    ball.mvt_dir = self.spin.transfer_to(
+                        normal,
+                        speed,
+                        ball.position,
+                        paddle.angular_velocity,
+                    );
+                    ball.curve = self.spin.curve_of(paddle.angular_velocity);
+                    events.push(Event::Released {
+                        paddle: attachment.paddle,
+                        ball: ball.id,
+                    });
+                    ball.attached = None;
+                }
+                continue;
+            }
+            if ball.curve != 0.0 {
+                let (sin, cos) = (ball.curve * delta_seconds).sin_cos();
+                let direction = ball.mvt_dir;
+                ball.mvt_dir = Vec2::new(
+                    direction.x * cos - direction.y * sin,
+                    direction.x * sin + direction.y * cos,
+                );
+                ball.curve = self.spin.fade(ball.curve, delta_seconds);
+            }
+            ball.position_previous = ball.position;
+            ball.position += (ball.velocity(&self.speed_curve) * delta_seconds) * ball.mvt_dir;
+            // after an impact, the rest of the move can reach an other ring or obstacle
+            let mut ball_translation_previous = ball.position_previous;
+            let mut fraction_left = 1.0;
+            // the surface just hit, the ball leaves it (ignore the impact at the start of the move)
+            let mut last_paddle = None;
+            let mut last_obstacle = None;
+            for impact in 0..MAX_IMPACTS_PER_STEP {
+                // the nearest impact of the move
+                let maybe_collision = self
+                    .paddles
+                    .iter()
+                    .enumerate()
+                    .filter(|(paddle_index, _)| last_paddle != Some(*paddle_index))
+                    .filter_map(|(paddle_index, paddle)| {
+                        // the start of the move relative to the ring where it is now, so a
+                        // ring coming toward the ball can't pass over it
+                        let previous = if impact == 0 && ring_motion != (0.0, 1.0) {
+                            paddle.follow_ring(
+                                ball_translation_previous,
+                                ring_motion.0,
+                                ring_motion.1,
+                            )
+                        } else {
+                            ball_translation_previous
+                        };
+                        find_ball_paddle_collision_point(&ball.position, &previous, ball, paddle)
+                            .map(|collision| (paddle_index, paddle, collision))
+                    })
+                    .min_by(|a, b| a.2.ratio.partial_cmp(&b.2.ratio).unwrap_or(Ordering::Equal));
+                let maybe_bounce = self
+                    .obstacles
+                    .iter()
+                    .enumerate()
+                    .filter(|(obstacle_index, _)| last_obstacle != Some(*obstacle_index))
+                    .filter_map(|(obstacle_index, obstacle)| {
+                        obstacle
+                            .find_collision(ball_translation_previous, ball.position, ball.radius)
+                            .map(|collision| (obstacle_index, obstacle, collision))
+                    })
+                    .min_by(|a, b| a.2.ratio.partial_cmp(&b.2.ratio).unwrap_or(Ordering::Equal))
+                    .filter(|bounce| {
+                        maybe_collision
+                            .as_ref()
+                            .is_none_or(|collision| bounce.2.ratio < collision.2.ratio)
+                    });
+                let collision = if let Some((obstacle_index, obstacle, collision)) = maybe_bounce {
+                    events.push(Event::Bounced {
+                        obstacle: obstacle_index,
+                        ball: ball.id,
+                        points: obstacle.points,
+                    });
+                    let surface_velocity = obstacle.velocity_at(collision.point)
+                        + self.arena.rotation_velocity_at(collision.point);
+                    ball.mvt_dir = reflect_on_moving(
+                        ball.mvt_dir,
+                        ball.velocity(&self.speed_curve),
+                        collision.normal,
+                        surface_velocity,
+                    );
+                    ball.velocity_indicator += obstacle.boost;
+                    last_paddle = None;
+                    last_obstacle = Some(obstacle_index);
+                    collision
+                } else if let Some((paddle_index, paddle, collision)) = maybe_collision {
+                    events.push(Event::Hit {
+                        paddle: paddle_index,
+                        ball: ball.id,
+                        direction: ball.mvt_dir,
+                        points: paddle.points,
+                    });
+                    ball.velocity_indicator += 1;
+                    ball.last_hit = Some(paddle.role);
+                    if paddle.magnetic {
+                        ball.position = collision.point;
+                        ball.attached = Some(Attachment {
+                            paddle: paddle_index,
+                            distance: paddle.attach_distance(collision.point),
+                            angle_offset: angle_delta(
+                                paddle.angle_origin,
+                                collision.point.y.atan2(collision.point.x),
+                            ),
+                            normal_offset: angle_delta(
+                                paddle.angle_origin,
+                                collision.normal.y.atan2(collision.normal.x),
+                            ),
+                            held_ticks: 0,
+                        });
+                        events.push(Event::Caught {
+                            paddle: paddle_index,
+                            ball: ball.id,
+                        });
+                        break;
+                    }
+                    let speed = ball.velocity(&self.speed_curve);
+                    ball.mvt_dir = reflect_on_moving(
+                        ball.mvt_dir,
+                        speed,
+                        collision.normal,
+                        self.arena.ring_velocity_at(collision.point),
+                    );
+                    // dragged along the surface of a turning paddle
+                    ball.mvt_dir = self.spin.transfer_to(
+                        ball.mvt_dir,
+                        speed,
+                        collision.point,
+                        paddle.angular_velocity,
+                    );
+                    ball.curve = self.spin.curve_of(paddle.angular_velocity);
+                    last_paddle = Some(paddle_index);
+                    last_obstacle = None;
+                    collision
+                } else {
+                    break;
+                };
+                fraction_left *= 1.0 - collision.ratio;
+                ball_translation_previous = collision.point;
+                ball.position = collision.point
+                    + (fraction_left * (ball.velocity(&self.speed_curve) * delta_seconds))
+                        * ball.mvt_dir;
+            }
+            for kind in self.power_ups.collect(ball) {
+                events.push(Event::Collected {
+                    ball: ball.id,
+                    kind,
+                });
+                if kind == PowerUpKind::Multiball {
+                    splits.push(ball.id);
+                }
+            }
+            if ball.teleport_cooldown > 0 {
+                ball.teleport_cooldown -= 1;
+            } else if let Some((teleporter_index, teleporter, entry)) = self
+                .teleporters
+                .iter()
+                .enumerate()
+                .find_map(|(index, teleporter)| {
+                    teleporter
+                        .entered(ball)
+                        .map(|entry| (index, teleporter, entry))
+                })
+            {
+                teleporter.teleport(ball, entry);
+                events.push(Event::Teleported {
+                    teleporter: teleporter_index,
+                    ball: ball.id,
+                });
+            }
+        }
+        self.collide_balls(&mut events);
+        if !self.balls.is_empty() {
+            let escapes: Vec<(BallId, Escape)> = self
+                .balls
+                .iter()
+                .filter_map(|ball| {
+                    let rings = (
+                        self.ring_radius_at(PaddleRole::Inner, ball.position),
+                        self.ring_radius_at(PaddleRole::Outer, ball.position),
+                    );
+                    find_escape(ball, rings).map(|escape| (ball.id, escape))
+                })
+                .collect();
+            self.balls
+                .retain(|ball| escapes.iter().all(|(escaped, _)| *escaped != ball.id));
+            for (ball, escape) in escapes {
+                events.push(Event::Escaped { ball, escape });
+                if self.play_mode == PlayMode::Versus {
+                    let role = escape.missed_by().opponent();
+                    self.scoreboard.versus.add_point(role);
+                    events.push(Event::Point { role });
+                }
+            }
+            if self.balls.is_empty() {
+                if self.play_mode == PlayMode::Versus
+                    && self
+                        .scoreboard
+                        .versus
+                        .winner(self.win.versus_points)
+                        .is_none()
+                {
+                    self.serve();
+                } else {
+                    self.commit_best();
+                    events.push(Event::GameOver);
+                }
+            }
+        }
+        for event in events.iter() {
+            hit_as_score(&mut self.scoreboard, event);
+        }
+        if let Some(milestone) = self.next_milestone {
+            if self.scoreboard.score >= milestone {
+                // from the ball that scored
+                let scorer = events.iter().rev().find_map(|event| match event {
+                    Event::Hit { ball, .. } | Event::Bounced { ball, .. } => Some(*ball),
+                    _ => None,
+                });
+                splits.extend(scorer);
+                self.next_milestone = self.multiball.next_milestone(self.scoreboard.score);
+            }
+        }
+        for from in splits {
+            if let Some((from, ball)) = self.split_ball(from) {
+                events.push(Event::Split { from, ball });
+            }
+        }
+        if self.is_won(delta_seconds) && !self.balls.is_empty() && !self.scoreboard.won {
+            self.scoreboard.won = true;
+            self.commit_best();
+            events.push(Event::GameOver);
+        }
+        self.power_ups.tick(self.tick);
+        self.apply_effects();
+        events
+    }
+
+    /// A win condition of solo / co-op is reached (versus is won by points).
+    fn is_won(&self, delta_seconds: f32) -> bool {
+        if self.play_mode == PlayMode::Versus {
+            return false;
+        }
+        let by_score = self
+            .win
+            .score
+            .is_some_and(|score| self.scoreboard.score >= score);
+        let by_survival = self
+            .win
+            .survive_seconds
+            .is_some_and(|seconds| self.tick as f32 * delta_seconds >= seconds);
+        by_score || by_survival
+    }
+
+    /// Bounce the balls in play on each other (the balls caught by a paddle don't move).
+    fn collide_balls(&mut self, events: &mut Vec<Event>) {
+        for i in 1..self.balls.len() {
+            let (before, after) = self.balls.split_at_mut(i);
+            let b = &mut after[0];
+            for a in before.iter_mut() {
+                if a.attached.is_none()
+                    && b.attached.is_none()
+                    && collide_balls(a, b, &self.speed_curve)
+                {
+                    events.push(Event::Collided {
+                        balls: [a.id, b.id],
+                    });
+                }
+            }
+        }
+    }
+
+    /// Turn and scale the rings, turn the obstacles and the teleporters with the arena.
+    fn place_arena(&mut self) {
+        let rotation = self.arena.rotation();
+        let scale = self.arena.scale();
+        for paddle in self.paddles.iter_mut() {
+            paddle.place(rotation, scale);
+        }
+        for obstacle in self.obstacles.iter_mut() {
+            obstacle.arena_angle = rotation;
+        }
+        for teleporter in self.teleporters.iter_mut() {
+            teleporter.arena_angle = rotation;
+        }
+        self.power_ups.rings = self.ring_bounds();
+    }
+
+    /// Move the auto-paddles toward the nearest ball (of their ring).
+    fn follow_balls(&mut self) {
+        for paddle in self.paddles.iter_mut().filter(|paddle| paddle.auto) {
+            let nearest = self.balls.iter().min_by(|a, b| {
+                let distance = |ball: &Ball| paddle.distance_to_ring(ball.position);
+                distance(a)
+                    .partial_cmp(&distance(b))
+                    .unwrap_or(Ordering::Equal)
+            });
+            if let Some(ball) = nearest {
+                paddle.set_angle(ball.position.y.atan2(ball.position.x));
+            }
+        }
+    }
+
+    /// Apply the active effects of the power-ups to the scoreboard, the balls and the paddles.
+    fn apply_effects(&mut self) {
+        self.scoreboard.multiplier = self.power_ups.score_multiplier();
+        let speed_factor = self.power_ups.speed_factor();
+        for ball in self.balls.iter_mut() {
+            ball.speed_factor = speed_factor;
+        }
+        for paddle in self.paddles.iter_mut() {
+            paddle.auto = self
+                .power_ups
+                .is_active_for(PowerUpKind::AutoPaddle, paddle.role);
+            paddle.magnetic = self
+                .power_ups
+                .is_active_for(PowerUpKind::Magnetic, paddle.role);
+        }
+    }
+}
+
+/// `rings`: `(inner radius, outer radius)` in the direction of the ball
+fn find_escape(ball: &Ball, rings: (f32, f32)) -> Option<Escape> {
+    // a caught ball stays on its paddle
+    if ball.attached.is_some() {
+        return None;
+    }
+    let o_dist = ball.position.length();
+    if o_dist > rings.1 {
+        Some(Escape::Outside)
+    } else if o_dist < rings.0 {
+        Some(Escape::Inside)
+    } else {
+        None
+    }
+}
+
+fn hit_as_score(scoreboard: &mut Scoreboard, event: &Event) {
+    match event {
+        Event::Hit { points, .. } => scoreboard.score += points * scoreboard.multiplier,
+        Event::Bounced { points, .. } => scoreboard.score += points * scoreboard.multiplier,
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Pulse;
+    use crate::control::ControlMode;
+    use crate::obstacle::Shape;
+    use crate::play_mode::VERSUS_POINTS;
+    use crate::teleporter::{Exit, Pad};
+    use crate::timestep::FixedTimestep;
+    use crate::track::TrackShape;
+
+    fn linked(angle: f32) -> Inputs {
+        ControlMode::default().dispatch(Some(angle), None)
+    }
+
+    #[test]
+    fn test_rally_without_window() {
+        let mut sim = Simulation::new();
+        sim.start();
+        // the first ball reaches the outer paddle around -1.29 rad
+        let inputs = linked(-1.29);
+        let mut hits = 0;
+        for _ in 0..60 {
+            hits += sim.step(1.0 / 60.0, &inputs).len();
+        }
+        assert!(hits > 0);
+        assert_eq!(sim.scoreboard.score, hits);
+    }
+
+    #[test]
+    fn test_game_over_when_the_ball_escapes() {
+        let mut sim = Simulation::new();
+        sim.scoreboard.score = 3;
+        sim.start();
+        // the paddles are on the opposite side of the ball
+        let inputs = linked(0.5 * PI);
+        let mut events = vec![];
+        for _ in 0..120 {
+            events.extend(sim.step(1.0 / 60.0, &inputs));
+        }
+        assert!(sim.balls.is_empty());
+        assert_eq!(sim.scoreboard.best, 3);
+        let escapes: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Escaped { escape, .. } => Some(*escape),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(escapes, vec![Escape::Outside]);
+        assert!(matches!(events.last(), Some(Event::GameOver)));
+    }
+
+    #[test]
+    fn test_serve_depends_on_the_seed() {
+        let serve = |seed| {
+            let mut sim = Simulation::new();
+            sim.seed = seed;
+            sim.start();
+            sim.balls[0].mvt_dir
+        };
+        assert_eq!(serve(0), Vec2::new(0.5, -0.5).normalize());
+        assert_eq!(serve(7), serve(7));
+        assert_ne!(serve(7), serve(8));
+    }
+
+    #[test]
+    fn test_independent_paddles() {
+        let mut sim = Simulation::new();
+        let inputs = ControlMode::Independent.dispatch(Some(1.0), Some(2.0));
+        sim.step(1.0 / 120.0, &inputs);
+        assert_eq!(sim.paddles[0].angle_origin, 1.0);
+        assert_eq!(sim.paddles[1].angle_origin, 2.0);
+        // without request, the inner paddle stays in place
+        let inputs = ControlMode::Independent.dispatch(Some(1.5), None);
+        sim.step(1.0 / 120.0, &inputs);
+        assert_eq!(sim.paddles[0].angle_origin, 1.5);
+        assert_eq!(sim.paddles[1].angle_origin, 2.0);
+        // linked with an offset, the inner paddle is on the opposite side
+        let inputs = ControlMode::Linked { inner_offset: PI }.dispatch(Some(0.5), Some(2.0));
+        sim.step(1.0 / 120.0, &inputs);
+        assert_eq!(sim.paddles[0].angle_origin, 0.5);
+        assert!((sim.paddles[1].angle_origin - (0.5 + PI)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_inner_angle_alone_in_linked_mode() {
+        let mut sim = Simulation::new();
+        sim.start();
+        let mode = ControlMode::default().next(0.5 * PI);
+        assert_eq!(
+            mode,
+            ControlMode::Linked {
+                inner_offset: 0.5 * PI
+            }
+        );
+        sim.step(1.0 / 120.0, &mode.dispatch(Some(0.5), None));
+        // the keyboard turns the inner paddle (from its angle), the outer paddle follows
+        for _ in 0..3 {
+            let inner_angle = sim.paddles[1].angle_origin + 0.1;
+            sim.step(1.0 / 120.0, &mode.dispatch(None, Some(inner_angle)));
+            assert!((sim.paddles[1].angle_origin - inner_angle).abs() < 1e-5);
+            assert!((sim.paddles[0].angle_origin - (inner_angle - 0.5 * PI)).abs() < 1e-5);
+        }
+        assert_eq!(mode.next(0.5 * PI), ControlMode::Independent);
+        // without offset, the second linked mode is skipped
+        assert_eq!(ControlMode::default().next(0.0), ControlMode::Independent);
+    }
+
+    #[test]
+    fn test_versus_point_for_the_opponent() {
+        let mut sim = Simulation::new();
+        sim.play_mode = PlayMode::Versus;
+        sim.start();
+        // the outer paddle is on the opposite side of the ball
+        let inputs = ControlMode::Independent.dispatch(Some(0.5 * PI), None);
+        let mut events = vec![];
+        while sim.scoreboard.versus.inner == 0 && sim.tick < 120 {
+            events.extend(sim.step(1.0 / 60.0, &inputs));
+        }
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Point {
+                role: PaddleRole::Inner
+            }
+        )));
+        assert_eq!(sim.scoreboard.versus.inner, 1);
+        assert_eq!(sim.scoreboard.versus.outer, 0);
+        // a new ball is served until a player wins
+        assert!(!events.iter().any(|e| matches!(e, Event::GameOver)));
+        assert_eq!(sim.balls.len(), 1);
+        while sim.scoreboard.versus.winner(VERSUS_POINTS).is_none() && sim.tick < 1200 {
+            events.extend(sim.step(1.0 / 60.0, &inputs));
+        }
+        assert_eq!(
+            sim.scoreboard.versus.winner(VERSUS_POINTS),
+            Some(PaddleRole::Inner)
+        );
+        assert!(matches!(events.last(), Some(Event::GameOver)));
+    }
+
+    #[test]
+    fn test_power_ups_effects() {
+        let mut sim = Simulation::new();
+        sim.start();
+        sim.power_ups.activate(PowerUpKind::DoubleScore, None);
+        sim.power_ups.activate(PowerUpKind::SlowBall, None);
+        sim.power_ups
+            .activate(PowerUpKind::AutoPaddle, Some(PaddleRole::Outer));
+        // the outer paddle follows the ball by itself, the hits score 2 points
+        let mut events = vec![];
+        for _ in 0..120 {
+            events.extend(sim.step(1.0 / 120.0, &Inputs::default()));
+        }
+        let hits = events
+            .iter()
+            .filter(|e| matches!(e, Event::Hit { .. }))
+            .count();
+        assert!(hits > 0);
+        assert_eq!(sim.scoreboard.score, 2 * hits);
+        assert!(sim.paddles[0].auto);
+        assert!(!sim.paddles[1].auto);
+        assert_eq!(sim.balls[0].speed_factor, 0.6);
+        // the effects end
+        for _ in 0..sim.power_ups.effect_ticks {
+            sim.step(1.0 / 120.0, &Inputs::default());
+        }
+        assert_eq!(sim.scoreboard.multiplier, 1);
+        assert!(!sim.paddles[0].auto);
+    }
+
+    #[test]
+    fn test_collect_a_zone() {
+        let mut sim = Simulation::new();
+        sim.start();
+        let ball = sim.balls[0].clone();
+        // a zone on the path of the ball
+        let id = sim.power_ups.spawn();
+        sim.power_ups.zones[0].kind = PowerUpKind::SlowBall;
+        sim.power_ups.zones[0].position = ball.position + ball.mvt_dir * 20.0;
+        let mut events = vec![];
+        for _ in 0..10 {
+            events.extend(sim.step(1.0 / 120.0, &Inputs::default()));
+        }
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Collected {
+                kind: PowerUpKind::SlowBall,
+                ..
+            }
+        )));
+        assert!(sim.power_ups.zones.iter().all(|zone| zone.id != id));
+        assert!(sim.balls[0].speed_factor < 1.0);
+    }
+
+    #[test]
+    fn test_concentric_rings() {
+        let mut sim = Simulation::new();
+        // a middle ring, driven by the outer player on the opposite side
+        let mut middle = Paddle::new(PaddleRole::Outer, 200.0, 12.0, FRAC_PI_6);
+        middle.offset = PI;
+        middle.points = 3;
+        sim.paddles.insert(1, middle);
+        assert_eq!(sim.driving_paddles().count(), 2);
+        assert_eq!(sim.ring_bounds(), (RADIUS_INTERN, RADIUS_EXTERN));
+        sim.start();
+        sim.balls[0].position = Vec2::new(0.0, 150.0);
+        sim.balls[0].mvt_dir = Vec2::new(0.0, 1.0);
+        let inputs = ControlMode::Independent.dispatch(Some(-0.5 * PI), Some(0.5 * PI));
+        // the paddles in place, without speed
+        sim.apply_inputs(&inputs);
+        sim.apply_inputs(&inputs);
+        // a long step: the ball bounces on the middle ring then on the inner ring
+        let events = sim.step(0.4, &inputs);
+        assert!((sim.paddles[1].angle_origin - 0.5 * PI).abs() < 1e-5);
+        assert!(matches!(
+            events[..],
+            [
+                Event::Hit {
+                    paddle: 1,
+                    points: 3,
+                    ..
+                },
+                Event::Hit { paddle: 2, .. }
+            ]
+        ));
+        assert_eq!(sim.scoreboard.score, 4);
+        let ball = &sim.balls[0];
+        assert!(ball.mvt_dir.y > 0.0 && ball.position.length() < 200.0);
+    }
+
+    #[test]
+    fn test_spin_of_a_turning_paddle() {
+        let mut sim = Simulation::new();
+        sim.spin.curve = 0.5;
+        sim.start();
+        sim.balls[0].position = Vec2::new(0.0, -200.0);
+        sim.balls[0].mvt_dir = Vec2::new(0.0, -1.0);
+        // the outer paddle turns counterclockwise at 2 radian per second
+        let mut angle = -0.5 * PI - 0.35;
+        sim.apply_inputs(&linked(angle));
+        let mut events = vec![];
+        for _ in 0..60 {
+            angle += 2.0 / 120.0;
+            events.extend(sim.step(1.0 / 120.0, &linked(angle)));
+            if !events.is_empty() {
+                break;
+            }
+        }
+        assert!(matches!(events[..], [Event::Hit { paddle: 0, .. }]));
+        assert!((sim.paddles[0].angular_velocity - 2.0).abs() < 0.1);
+        // dragged counterclockwise, and curving
+        let ball = &sim.balls[0];
+        let tangent = Vec2::new(-ball.position.y, ball.position.x).normalize();
+        assert!(ball.mvt_dir.dot(tangent) > 0.05);
+        assert!((ball.curve - 1.0).abs() < 0.05);
+        let direction = ball.mvt_dir;
+        sim.step(1.0 / 120.0, &linked(angle));
+        let turned = direction.angle_between(sim.balls[0].mvt_dir);
+        assert!((turned - 1.0 / 120.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_bounce_on_a_pulsing_ring() {
+        let mut sim = Simulation::new();
+        sim.arena.pulse = Some(Pulse {
+            amplitude: 0.1,
+            period: 2.0,
+        });
+        sim.start();
+        // the rings are shrinking
+        sim.arena.elapsed = 0.9;
+        sim.balls[0].position = Vec2::new(250.0, -20.0);
+        sim.balls[0].mvt_dir = Vec2::new(0.8, 0.6);
+        let inputs = ControlMode::Independent.dispatch(Some(0.0), Some(PI));
+        let mut events = vec![];
+        for _ in 0..60 {
+            events.extend(sim.step(1.0 / 120.0, &inputs));
+            if !events.is_empty() {
+                break;
+            }
+        }
+        let direction = match events[..] {
+            [Event::Hit {
+                paddle: 0,
+                direction,
+                ..
+            }] => direction,
+            _ => panic!("no hit: {:?}", events),
+        };
+        assert!(sim.paddles[0].radius_origin > RADIUS_EXTERN);
+        // pushed back by the ring: faster toward the center than it came
+        let radial = sim.balls[0].position.normalize();
+        assert!(-sim.balls[0].mvt_dir.dot(radial) > direction.dot(radial) + 0.01);
+    }
+
+    #[test]
+    fn test_radial_shots_at_a_shrinking_ring() {
+        let mut sim = Simulation::new();
+        sim.arena.pulse = Some(Pulse {
+            amplitude: 0.06,
+            period: 5.0,
+        });
+        for shot in 0..400 {
+            sim.start();
+            // the rings are shrinking between 1.25s and 3.75s
+            sim.arena.elapsed = 1.25 + 2.5 * shot as f32 / 400.0;
+            sim.place_arena();
+            let angle = shot as f32 * 0.1;
+            let direction = Vec2::new(angle.cos(), angle.sin());
+            sim.balls[0].position = direction * (200.0 + (shot % 17) as f32 * 0.2);
+            sim.balls[0].mvt_dir = direction;
+            let inputs = ControlMode::Independent.dispatch(Some(angle), Some(angle + PI));
+            let mut events = vec![];
+            while events.is_empty() && sim.tick < 60 {
+                events.extend(sim.step(1.0 / 120.0, &inputs));
+            }
+            assert!(
+                matches!(events[..], [Event::Hit { paddle: 0, .. }]),
+                "shot {}: {:?}",
+                shot,
+                events
+            );
+        }
+    }
+
+    #[test]
+    fn test_the_arena_turns() {
+        let mut sim = Simulation::new();
+        sim.obstacles
+            .push(Obstacle::bumper(Vec2::new(200.0, 0.0), 10.0));
+        sim.arena.angular_speed = 0.5 * PI;
+        sim.start();
+        for _ in 0..120 {
+            sim.step(1.0 / 120.0, &Inputs::default());
+        }
+        match sim.obstacles[0].shape_now() {
+            Shape::Bumper { center, .. } => {
+                assert!((center - Vec2::new(0.0, 200.0)).length() < 1e-2)
+            }
+            _ => unreachable!(),
+        }
+        // restarted at rest
+        sim.start();
+        assert_eq!(sim.obstacles[0].shape_now(), sim.obstacles[0].shape);
+    }
+
+    #[test]
+    fn test_bounce_on_a_track() {
+        let mut sim = Simulation::new();
+        sim.paddles[0] = Paddle::new(PaddleRole::Outer, RADIUS_EXTERN, 12.0, FRAC_PI_6).with_track(
+            TrackShape::RoundedSquare {
+                half_size: 250.0,
+                corner_radius: 40.0,
+            },
+        );
+        sim.start();
+        sim.balls[0].position = Vec2::new(-60.0, 160.0);
+        sim.balls[0].mvt_dir = Vec2::new(0.6, 0.8);
+        let inputs = linked(0.5 * PI);
+        let mut events = vec![];
+        for _ in 0..60 {
+            events.extend(sim.step(1.0 / 120.0, &inputs));
+            if !events.is_empty() {
+                break;
+            }
+        }
+        assert!(matches!(events[..], [Event::Hit { paddle: 0, .. }]));
+        // reflected by the flat side (a circle would have sent it to the left)
+        assert!((sim.balls[0].mvt_dir - Vec2::new(0.6, -0.8)).length() < 1e-3);
+        // beyond the side, but nearer than the reference radius
+        sim.balls[0].position = Vec2::new(-150.0, 255.0);
+        sim.balls[0].mvt_dir = Vec2::new(0.0, 1.0);
+        let events = sim.step(1.0 / 120.0, &linked(-0.5 * PI));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Escaped {
+                escape: Escape::Outside,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn test_bounce_on_a_bumper() {
+        let mut sim = Simulation::new();
+        sim.start();
+        let ball = sim.balls[0].clone();
+        // a bumper on the path of the ball
+        sim.obstacles.push(Obstacle {
+            boost: 3,
+            points: 5,
+            ..Obstacle::bumper(ball.position + ball.mvt_dir * 30.0, 10.0)
+        });
+        let mut events = vec![];
+        for _ in 0..10 {
+            events.extend(sim.step(1.0 / 120.0, &Inputs::default()));
+        }
+        assert!(matches!(
+            events[..],
+            [Event::Bounced {
+                obstacle: 0,
+                points: 5,
+                ..
+            }]
+        ));
+        assert_eq!(sim.scoreboard.score, 5);
+        assert_eq!(sim.balls[0].velocity_indicator, 3);
+        assert!((sim.balls[0].mvt_dir + ball.mvt_dir).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_win_by_score() {
+        let mut sim = Simulation::new();
+        sim.win.score = Some(5);
+        sim.start();
+        let ball = sim.balls[0].clone();
+        sim.obstacles.push(Obstacle {
+            points: 5,
+            ..Obstacle::bumper(ball.position + ball.mvt_dir * 30.0, 10.0)
+        });
+        let mut events = vec![];
+        for _ in 0..10 {
+            events.extend(sim.step(1.0 / 120.0, &Inputs::default()));
+        }
+        assert!(matches!(events.last(), Some(Event::GameOver)));
+        assert!(sim.scoreboard.won);
+        assert_eq!(sim.scoreboard.best, 5);
+        // only one game over
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| matches!(e, Event::GameOver))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_serve_from_the_spawns() {
+        let mut sim = Simulation::new();
+        sim.spawns = vec![
+            Spawn {
+                position: Vec2::new(0.0, 200.0),
+                direction: Vec2::new(1.0, 0.0),
+            },
+            Spawn::default(),
+        ];
+        sim.start();
+        assert_eq!(sim.balls[0].position, Vec2::new(0.0, 200.0));
+        assert_eq!(sim.balls[0].mvt_dir, Vec2::new(1.0, 0.0));
+        // shuffled by the seed
+        let positions: Vec<_> = (1..20)
+            .map(|seed| {
+                sim.seed = seed;
+                sim.start();
+                sim.balls[0].position
+            })
+            .collect();
+        assert!(positions.contains(&Vec2::new(0.0, 200.0)));
+        assert!(positions.contains(&Spawn::default().position));
+    }
+
+    #[test]
+    fn test_teleport_with_cooldown() {
+        let mut sim = Simulation::new();
+        sim.start();
+        let ball = sim.balls[0].clone();
+        // the exit pad is on the path of the teleported ball
+        let entry = ball.position + ball.mvt_dir * 20.0;
+        sim.teleporters.push(Teleporter::new(
+            Pad {
+                center: entry,
+                radius: 10.0,
+                angle: 0.0,
+            },
+            Pad {
+                center: entry + ball.mvt_dir * 30.0,
+                radius: 10.0,
+                angle: 0.0,
+            },
+            Exit::Keep,
+        ));
+        // the ball is on the exit pad for ~6 steps
+        let mut events = vec![];
+        for _ in 0..12 {
+            events.extend(sim.step(1.0 / 120.0, &Inputs::default()));
+        }
+        // teleported once, not back from the exit pad
+        assert!(matches!(
+            events[..],
+            [Event::Teleported { teleporter: 0, .. }]
+        ));
+        assert_eq!(sim.balls[0].mvt_dir, ball.mvt_dir);
+        assert!(sim.balls[0].teleport_cooldown > 0);
+    }
+
+    #[test]
+    fn test_multiball_zone_splits_the_ball() {
+        let mut sim = Simulation::new();
+        sim.multiball.enabled = true;
+        sim.start();
+        let ball = sim.balls[0].clone();
+        sim.power_ups.spawn();
+        sim.power_ups.zones[0].kind = PowerUpKind::Multiball;
+        sim.power_ups.zones[0].position = ball.position + ball.mvt_dir * 20.0;
+        let mut events = vec![];
+        for _ in 0..10 {
+            events.extend(sim.step(1.0 / 120.0, &Inputs::default()));
+        }
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::Split { from, .. } if *from == ball.id)));
+        assert_eq!(sim.balls.len(), 2);
+        // no timed effect
+        assert!(sim.power_ups.effects.is_empty());
+        // the balls go apart
+        assert!(sim.balls[0].mvt_dir.dot(sim.balls[1].mvt_dir) < 0.9);
+    }
+
+    #[test]
+    fn test_extra_ball_on_score_milestone() {
+        let mut sim = Simulation::new();
+        sim.multiball = Multiball {
+            enabled: true,
+            every_points: 5,
+            max_balls: 2,
+        };
+        sim.start();
+        sim.balls[0].velocity_indicator = 4;
+        let ball = sim.balls[0].clone();
+        sim.obstacles.push(Obstacle {
+            points: 5,
+            ..Obstacle::bumper(ball.position + ball.mvt_dir * 30.0, 10.0)
+        });
+        let mut events = vec![];
+        for _ in 0..10 {
+            events.extend(sim.step(1.0 / 120.0, &Inputs::default()));
+        }
+        assert!(events.iter().any(|e| matches!(e, Event::Split { .. })));
+        assert_eq!(sim.balls.len(), 2);
+        // each ball keeps its own velocity
+        assert_eq!(sim.balls[0].velocity_indicator, 4);
+        assert_eq!(sim.balls[1].velocity_indicator, 0);
+        // no more than `max_balls`
+        assert!(sim.split_ball(ball.id).is_none());
+    }
+
+    #[test]
+    fn test_game_over_with_the_last_ball() {
+        let mut sim = Simulation::new();
+        sim.multiball.enabled = true;
+        sim.start();
+        let first = sim.balls[0].id;
+        sim.split_ball(first);
+        // the paddles are on the opposite side of the balls
+        let inputs = ControlMode::Independent.dispatch(Some(0.5 * PI), Some(0.5 * PI));
+        let mut events = vec![];
+        while !events.iter().any(|e| matches!(e, Event::GameOver)) && sim.tick < 240 {
+            events.extend(sim.step(1.0 / 120.0, &inputs));
+        }
+        let escaped = events
+            .iter()
+            .filter(|e| matches!(e, Event::Escaped { .. }))
+            .count();
+        assert_eq!(escaped, 2);
+        assert!(matches!(events.last(), Some(Event::GameOver)));
+    }
+
+    #[test]
+    fn test_balls_bounce_on_each_other() {
+        let mut sim = Simulation::new();
+        let distance = (RADIUS_EXTERN + RADIUS_INTERN) / 2.0;
+        let a = sim.spawn_ball(Vec2::new(-10.0, distance), Vec2::new(1.0, 0.0));
+        let b = sim.spawn_ball(Vec2::new(10.0, distance), Vec2::new(-1.0, 0.0));
+        let mut events = vec![];
+        for _ in 0..5 {
+            events.extend(sim.step(1.0 / 120.0, &Inputs::default()));
+        }
+        assert!(matches!(
+            events[..],
+            [Event::Collided { balls }] if balls == [a, b]
+        ));
+        assert_eq!(sim.balls[0].mvt_dir, Vec2::new(-1.0, 0.0));
+        assert_eq!(sim.balls[1].mvt_dir, Vec2::new(1.0, 0.0));
+    }
+
+    /// Step until the outer paddle (magnetic) catches the first ball, return the events.
+    fn catch_the_ball(sim: &mut Simulation) -> Vec<Event> {
+        sim.start();
+        sim.power_ups
+            .activate(PowerUpKind::Magnetic, Some(PaddleRole::Outer));
+        // the first ball reaches the outer paddle around -1.29 rad
+        let inputs = ControlMode::Independent.dispatch(Some(-1.29), Some(PI));
+        let mut events = vec![];
+        while !events.iter().any(|e| matches!(e, Event::Caught { .. })) && sim.tick < 120 {
+            events.extend(sim.step(1.0 / 120.0, &inputs));
+        }
+        events
+    }
+
+    #[test]
+    fn test_magnetic_catch_and_release() {
+        let mut sim = Simulation::new();
+        let events = catch_the_ball(&mut sim);
+        assert!(sim.balls[0].attached.is_some());
+        assert!(matches!(
+            events.iter().rev().nth(1),
+            Some(Event::Hit { paddle: 0, .. })
+        ));
+        // the ball moves with the paddle
+        let angle = |ball: &Ball| ball.position.y.atan2(ball.position.x);
+        let caught_at = angle(&sim.balls[0]);
+        let mut inputs = ControlMode::Independent.dispatch(Some(-1.19), None);
+        sim.step(1.0 / 120.0, &inputs);
+        assert!((angle(&sim.balls[0]) - (caught_at + 0.1)).abs() < 1e-4);
+        // released while the paddle turns counterclockwise, the ball leaves toward the center,
+        // deviated in the direction of the rotation
+        inputs.outer_angle = Some(-1.14);
+        inputs.set_release(PaddleRole::Outer);
+        let events = sim.step(1.0 / 120.0, &inputs);
+        assert!(matches!(events[0], Event::Released { paddle: 0, .. }));
+        let ball = &sim.balls[0];
+        assert!(ball.attached.is_none());
+        assert!(ball.mvt_dir.dot(ball.position.normalize()) < 0.0);
+        let tangent = Vec2::new(-ball.position.y, ball.position.x).normalize();
+        assert!(ball.mvt_dir.dot(tangent) > 0.0);
+        // without release, the paddle lets the ball go after a while
+        catch_the_ball(&mut sim);
+        for _ in 1..sim.power_ups.hold_ticks {
+            sim.step(1.0 / 120.0, &Inputs::default());
+        }
+        assert!(sim.balls[0].attached.is_some());
+        sim.step(1.0 / 120.0, &Inputs::default());
+        assert!(sim.balls[0].attached.is_none());
+    }
+
+    fn run_at_frame_rate(frame_seconds: f32, ticks: u64) -> Simulation {
+        let mut sim = Simulation::new();
+        sim.start();
+        let mut timestep = FixedTimestep::default();
+        while sim.tick < ticks {
+            for _ in 0..timestep.advance(frame_seconds) {
+                // the paddles follow the ball
+                let inputs = ControlMode::default().dispatch(
+                    sim.balls
+                        .first()
+                        .map(|ball| ball.position.y.atan2(ball.position.x)),
+                    None,
+                );
+                sim.step(timestep.step_seconds, &inputs);
+                if sim.tick == ticks {
+                    break;
+                }
+            }
+        }
+        sim
+    }
+
+    #[test]
+    fn test_same_trajectory_whatever_the_frame_rate() {
+        let expected = run_at_frame_rate(1.0 / 60.0, 600);
+        for frame_seconds in &[1.0 / 144.0, 1.0 / 30.0, 0.2] {
+            let sim = run_at_frame_rate(*frame_seconds, 600);
+            assert_eq!(sim.balls.len(), expected.balls.len());
+            for (ball, expected_ball) in sim.balls.iter().zip(expected.balls.iter()) {
+                assert_eq!(ball.position, expected_ball.position);
+                assert_eq!(ball.mvt_dir, expected_ball.mvt_dir);
+            }
+            assert_eq!(sim.scoreboard.score, expected.scoreboard.score);
+        }
+        assert!(expected.scoreboard.score > 0);
+    }
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How the velocity of a ball grows with its `velocity_indicator` (the number of impacts).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SpeedCurve {
+    /// `base + step * i`
+    Linear { base: f32, step: f32 },
+    /// `base + factor * ln(1 + i)`
+    Logarithmic { base: f32, factor: f32 },
+    /// `base + factor * i * i`
+    Quadratic { base: f32, factor: f32 },
+    /// cubic bezier between `start` (at 0) and `end` (at `hits` and after), shaped by 2 control values
+    Bezier {
+        hits: f32,
+        start: f32,
+        control1: f32,
+        control2: f32,
+        end: f32,
+    },
+    /// the `curve` but never above `max`
+    Capped { curve: Box<SpeedCurve>, max: f32 },
+}
+
+impl Default for SpeedCurve {
+    fn default() -> Self {
+        SpeedCurve::Linear {
+            base: 410.0,
+            step: 10.0,
+        }
+    }
+}
+
+impl SpeedCurve {
+    pub fn velocity(&self, velocity_indicator: i32) -> f32 {
+        let i = velocity_indicator.max(0) as f32;
+        match self {
+            SpeedCurve::Linear { base, step } => base + step * i,
+            SpeedCurve::Logarithmic { base, factor } => base + factor * i.ln_1p(),
+            SpeedCurve::Quadratic { base, factor } => base + factor * i * i,
+            SpeedCurve::Bezier {
+                hits,
+                start,
+                control1,
+                control2,
+                end,
+            } => {
+                let t = if *hits > 0.0 {
+                    (i / hits).min(1.0)
+                } else {
+                    1.0
+                };
+                let u = 1.0 - t;
+                u * u * u * start
+                    + 3.0 * u * u * t * control1
+                    + 3.0 * u * t * t * control2
+                    + t * t * t * end
+            }
+            SpeedCurve::Capped { curve, max } => curve.velocity(velocity_indicator).min(*max),
+        }
+    }
+
+    pub fn capped(self, max: f32) -> Self {
+        SpeedCurve::Capped {
+            curve: Box::new(self),
+            max,
+        }
+    }
+}
+
+impl fmt::Display for SpeedCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpeedCurve::Linear { base, step } => write!(f, "linear: {} + {} * i", base, step),
+            SpeedCurve::Logarithmic { base, factor } => {
+                write!(f, "log: {} + {} * ln(1 + i)", base, factor)
+            }
+            SpeedCurve::Quadratic { base, factor } => {
+                write!(f, "quadratic: {} + {} * i^2", base, factor)
+            }
+            SpeedCurve::Bezier {
+                hits,
+                start,
+                control1,
+                control2,
+                end,
+            } => write!(
+                f,
+                "bezier: ({}, {}, {}, {}) over {} hits",
+                start, control1, control2, end, hits
+            ),
+            SpeedCurve::Capped { curve, max } => write!(f, "{} (max {})", curve, max),
+        }
+    }
+}
+
+/// Each difficulty comes with its own `SpeedCurve`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn speed_curve(&self) -> SpeedCurve {
+        match self {
+            Difficulty::Easy => SpeedCurve::Logarithmic {
+                base: 350.0,
+                factor: 80.0,
+            }
+            .capped(650.0),
+            Difficulty::Normal => SpeedCurve::default(),
+            Difficulty::Hard => SpeedCurve::Bezier {
+                hits: 40.0,
+                start: 450.0,
+                control1: 450.0,
+                control2: 1100.0,
+                end: 1100.0,
+            },
+        }
+    }
+
+    pub fn next(&self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_the_historical_curve() {
+        let curve = SpeedCurve::default();
+        assert_eq!(curve.velocity(0), 410.0);
+        assert_eq!(curve.velocity(3), 440.0);
+    }
+
+    #[test]
+    fn test_bezier_bounds() {
+        let curve = Difficulty::Hard.speed_curve();
+        assert_eq!(curve.velocity(0), 450.0);
+        assert_eq!(curve.velocity(40), 1100.0);
+        assert_eq!(curve.velocity(100), 1100.0);
+        assert!(curve.velocity(10) < curve.velocity(20));
+    }
+
+    #[test]
+    fn test_each_difficulty_has_its_curve() {
+        let mut difficulty = Difficulty::default();
+        let mut curves = vec![];
+        for _ in 0..3 {
+            curves.push(difficulty.speed_curve());
+            difficulty = difficulty.next();
+        }
+        assert_eq!(difficulty, Difficulty::default());
+        assert_ne!(curves[0], curves[1]);
+        assert_ne!(curves[1], curves[2]);
+        assert_ne!(curves[2], curves[0]);
+    }
+
+    #[test]
+    fn test_capped() {
+        let curve = SpeedCurve::Quadratic {
+            base: 400.0,
+            factor: 1.0,
+        }
+        .capped(500.0);
+        assert_eq!(curve.velocity(5), 425.0);
+        assert_eq!(curve.velocity(50), 500.0);
+    }
+}
@@ -0,0 +1,190 @@
+//! Spin ("english"): a turning paddle drags the ball it hits along its surface, and can make it
+//! curve.
+//!
+//! The angular velocity of a paddle is measured on its moves at every step of the simulation
+//! (whatever drives it: mouse, gamepad, AI, replay) and filtered, so it doesn't depend on how often
+//! the devices send their events.
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spin {
+    /// fraction of the velocity of the surface of the paddle (along the ring) given to the ball
+    /// by a hit, `0` for a plain reflection
+    #[serde(default = "default_transfer")]
+    pub transfer: f32,
+    /// the direction of a ball hit by a turning paddle turns at `curve` times the angular
+    /// velocity of the paddle (radian per second), `0` for straight moves
+    #[serde(default)]
+    pub curve: f32,
+    /// seconds for the curve of the ball to fade (by e)
+    #[serde(default = "default_curve_seconds")]
+    pub curve_seconds: f32,
+    /// time constant (seconds) of the filter of the angular velocity of the paddles, `0` for the
+    /// move of the last step only
+    #[serde(default = "default_smoothing_seconds")]
+    pub smoothing_seconds: f32,
+    /// the angular velocity (radian per second) given to the balls is capped, so a flick of the
+    /// mouse (a jump of the paddle in one step) doesn't throw them
+    #[serde(default = "default_max_angular_velocity")]
+    pub max_angular_velocity: f32,
+}
+
+fn default_transfer() -> f32 {
+    0.1
+}
+
+fn default_curve_seconds() -> f32 {
+    1.0
+}
+
+fn default_smoothing_seconds() -> f32 {
+    0.03
+}
+
+fn default_max_angular_velocity() -> f32 {
+    2.0 * std::f32::consts::PI
+}
+
+impl Default for Spin {
+    fn default() -> Self {
+        Spin {
+            transfer: default_transfer(),
+            curve: 0.0,
+            curve_seconds: default_curve_seconds(),
+            smoothing_seconds: default_smoothing_seconds(),
+            max_angular_velocity: default_max_angular_velocity(),
+        }
+    }
+}
+
+impl Spin {
+    /// The filtered angular velocity, from the previous one and the one `measured` during the
+    /// last step.
+    pub fn filter(&self, angular_velocity: f32, measured: f32, delta_seconds: f32) -> f32 {
+        if self.smoothing_seconds <= 0.0 {
+            return measured;
+        }
+        let weight = 1.0 - (-delta_seconds / self.smoothing_seconds).exp();
+        angular_velocity + (measured - angular_velocity) * weight
+    }
+
+    /// The direction of a ball that leaves (in `direction`, at `speed`) a paddle turning at
+    /// `angular_velocity`, from `point`.
+    pub fn transfer_to(
+        &self,
+        direction: Vec2,
+        speed: f32,
+        point: Vec2,
+        angular_velocity: f32,
+    ) -> Vec2 {
+        if angular_velocity == 0.0 || self.transfer == 0.0 {
+            return direction;
+        }
+        let angular_velocity = self.capped(angular_velocity);
+        let surface_velocity = Vec2::new(-point.y, point.x) * angular_velocity;
+        let velocity = direction * speed + surface_velocity * self.transfer;
+        if velocity.length_squared() > 0.0 {
+            velocity.normalize()
+        } else {
+            direction
+        }
+    }
+
+    /// The curve (radian per second) of a ball hit by a paddle turning at `angular_velocity`.
+    pub fn curve_of(&self, angular_velocity: f32) -> f32 {
+        self.curve * self.capped(angular_velocity)
+    }
+
+    fn capped(&self, angular_velocity: f32) -> f32 {
+        angular_velocity.clamp(-self.max_angular_velocity, self.max_angular_velocity)
+    }
+
+    /// The curve of a ball after `delta_seconds`.
+    pub fn fade(&self, curve: f32, delta_seconds: f32) -> f32 {
+        if self.curve_seconds > 0.0 {
+            curve * (-delta_seconds / self.curve_seconds).exp()
+        } else {
+            curve
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let positive_or_zero = |value: f32| value >= 0.0 && value.is_finite();
+        if !positive_or_zero(self.transfer) {
+            return Err("transfer should be >= 0".to_string());
+        }
+        if !self.curve.is_finite() {
+            return Err("curve should be a number".to_string());
+        }
+        if !positive_or_zero(self.curve_seconds) || !positive_or_zero(self.smoothing_seconds) {
+            return Err("curve_seconds and smoothing_seconds should be >= 0".to_string());
+        }
+        if !(self.max_angular_velocity > 0.0 && self.max_angular_velocity.is_finite()) {
+            return Err("max_angular_velocity should be > 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_filtered_angular_velocity() {
+        let spin = Spin::default();
+        // a mouse that moves the paddle every other step
+        let mut angular_velocity = 0.0;
+        for step in 0..120 {
+            let measured = if step % 2 == 0 { 4.0 } else { 0.0 };
+            angular_velocity = spin.filter(angular_velocity, measured, 1.0 / 120.0);
+        }
+        assert!((angular_velocity - 2.0).abs() < 0.5);
+        let raw = Spin {
+            smoothing_seconds: 0.0,
+            ..Spin::default()
+        };
+        assert_eq!(raw.filter(2.0, 4.0, 1.0 / 120.0), 4.0);
+    }
+
+    #[test]
+    fn test_flick_is_capped() {
+        let spin = Spin {
+            curve: 0.5,
+            ..Spin::default()
+        };
+        let direction = Vec2::new(-1.0, 0.0);
+        let point = Vec2::new(285.0, 0.0);
+        // the paddle jumped by half a turn in one step
+        let flick = spin.transfer_to(direction, 400.0, point, PI * 120.0);
+        let max = spin.transfer_to(direction, 400.0, point, spin.max_angular_velocity);
+        assert_eq!(flick, max);
+        assert!(flick.x < -0.5);
+        assert_eq!(spin.curve_of(-PI * 120.0), -0.5 * spin.max_angular_velocity);
+    }
+
+    #[test]
+    fn test_transfer_along_the_surface() {
+        let spin = Spin::default();
+        let direction = Vec2::new(-1.0, 0.0);
+        // at the right of the center, the surface of a counterclockwise paddle goes up
+        let thrown = spin.transfer_to(direction, 400.0, Vec2::new(100.0, 0.0), 5.0);
+        assert!(thrown.y > 0.1 && thrown.x < 0.0);
+        assert_eq!(
+            spin.transfer_to(direction, 400.0, Vec2::new(100.0, 0.0), 0.0),
+            direction
+        );
+        let none = Spin {
+            transfer: 0.0,
+            ..Spin::default()
+        };
+        assert_eq!(
+            none.transfer_to(direction, 400.0, Vec2::new(100.0, 0.0), 20.0),
+            direction
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Where the `HighScores` (and the replay of the rounds) are kept between 2 sessions: files in the
+//! user data directory on native, the `localStorage` of the browser on wasm32.
+
+use crate::high_scores::HighScores;
+use crate::replay::{Replay, ReplayError};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// no place to store anything (no home directory, private browsing, ...)
+    Unavailable(String),
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Replay(ReplayError),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Unavailable(msg) => write!(f, "storage unavailable: {}", msg),
+            StorageError::Io(err) => write!(f, "failed to read / write the storage: {}", err),
+            StorageError::Format(err) => write!(f, "invalid high scores: {}", err),
+            StorageError::Replay(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError::Format(err)
+    }
+}
+
+impl From<ReplayError> for StorageError {
+    fn from(err: ReplayError) -> Self {
+        StorageError::Replay(err)
+    }
+}
+
+pub trait HighScoresStorage {
+    /// The stored high scores, an empty table if nothing was stored yet.
+    fn load(&self) -> Result<HighScores, StorageError>;
+    fn save(&self, high_scores: &HighScores) -> Result<(), StorageError>;
+}
+
+/// The storage of the current platform.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_storage() -> Result<FileStorage, StorageError> {
+    FileStorage::in_user_data_dir()
+}
+
+/// The storage of the current platform.
+#[cfg(target_arch = "wasm32")]
+pub fn default_storage() -> Result<LocalStorage, StorageError> {
+    Ok(LocalStorage::default())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn user_data_dir() -> Result<std::path::PathBuf, StorageError> {
+    dirs::data_dir()
+        .map(|dir| dir.join("ld47_keep_inside"))
+        .ok_or_else(|| StorageError::Unavailable("no user data directory".to_string()))
+}
+
+/// Save the replay of a round, return where it's saved.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_replay(replay: &Replay) -> Result<String, StorageError> {
+    let dir = user_data_dir()?.join("replays");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.replay", replay.config.seed));
+    replay.write(std::io::BufWriter::new(std::fs::File::create(&path)?))?;
+    Ok(path.display().to_string())
+}
+
+/// Save the replay of a round, return where it's saved (only the last replay is kept).
+#[cfg(target_arch = "wasm32")]
+pub fn save_replay(replay: &Replay) -> Result<String, StorageError> {
+    let key = "ld47_keep_inside.last_replay";
+    let mut content = vec![];
+    replay.write(&mut content)?;
+    LocalStorage::default()
+        .storage()?
+        .set_item(key, &String::from_utf8_lossy(&content))
+        .map_err(|err| StorageError::Unavailable(format!("{:?}", err)))?;
+    Ok(format!("localStorage '{}'", key))
+}
+
+/// Seconds since the unix epoch, to date the high scores.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_unix_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Seconds since the unix epoch, to date the high scores.
+#[cfg(target_arch = "wasm32")]
+pub fn now_unix_seconds() -> u64 {
+    // `SystemTime::now()` panics on wasm32
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileStorage {
+    pub path: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    pub fn in_user_data_dir() -> Result<Self, StorageError> {
+        Ok(FileStorage {
+            path: user_data_dir()?.join("high_scores.json"),
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HighScoresStorage for FileStorage {
+    fn load(&self) -> Result<HighScores, StorageError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(json) => Ok(HighScores::from_json(&json)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HighScores::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&self, high_scores: &HighScores) -> Result<(), StorageError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, high_scores.to_json()?)?;
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub struct LocalStorage {
+    pub key: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Default for LocalStorage {
+    fn default() -> Self {
+        LocalStorage {
+            key: "ld47_keep_inside.high_scores".to_string(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorage {
+    // `web_sys::Storage` is not `Send`, so it's retrieved on each access
+    fn storage(&self) -> Result<web_sys::Storage, StorageError> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| StorageError::Unavailable("no localStorage".to_string()))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl HighScoresStorage for LocalStorage {
+    fn load(&self) -> Result<HighScores, StorageError> {
+        let json = self
+            .storage()?
+            .get_item(&self.key)
+            .map_err(|err| StorageError::Unavailable(format!("{:?}", err)))?;
+        match json {
+            Some(json) => Ok(HighScores::from_json(&json)?),
+            None => Ok(HighScores::default()),
+        }
+    }
+
+    fn save(&self, high_scores: &HighScores) -> Result<(), StorageError> {
+        self.storage()?
+            .set_item(&self.key, &high_scores.to_json()?)
+            .map_err(|err| StorageError::Unavailable(format!("{:?}", err)))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::high_scores::HighScore;
+    use crate::play_mode::PlayMode;
+
+    #[test]
+    fn test_file_storage_roundtrip() {
+        let storage = FileStorage {
+            path: std::env::temp_dir()
+                .join(format!("ld47_keep_inside_test_{}", std::process::id()))
+                .join("high_scores.json"),
+        };
+        // nothing stored yet
+        assert_eq!(storage.load().unwrap(), HighScores::default());
+        let mut high_scores = HighScores::default();
+        high_scores.insert(HighScore {
+            score: 12,
+            date: now_unix_seconds(),
+            mode: PlayMode::Solo,
+            seed: 3,
+        });
+        storage.save(&high_scores).unwrap();
+        assert_eq!(storage.load().unwrap(), high_scores);
+        std::fs::remove_dir_all(storage.path.parent().unwrap()).unwrap();
+    }
+}
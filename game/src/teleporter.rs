@@ -0,0 +1,159 @@
+//! Pairs of linked pads: a ball entering a pad comes out of the other one.
+
+use crate::ball::Ball;
+use crate::physics::distance_to_segment;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pad {
+    pub center: Vec2,
+    pub radius: f32,
+    /// orientation of the pad (radian)
+    #[serde(default)]
+    pub angle: f32,
+}
+
+/// Direction of the ball when it comes out of a pad.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Exit {
+    /// the same direction as when it entered
+    #[default]
+    Keep,
+    /// the same direction relative to the orientation of the pads
+    Rotate,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Teleporter {
+    pub pads: [Pad; 2],
+    #[serde(default)]
+    pub exit: Exit,
+    /// steps during which a teleported ball can't be teleported again, so it can leave the pad
+    #[serde(default = "default_cooldown_ticks")]
+    pub cooldown_ticks: u64,
+    /// rotation of the arena (see `ArenaMotion`), the pads turn with it
+    #[serde(skip)]
+    pub arena_angle: f32,
+}
+
+fn default_cooldown_ticks() -> u64 {
+    // 0.25s at 120 steps per second (see `FixedTimestep`)
+    30
+}
+
+impl Teleporter {
+    pub fn new(a: Pad, b: Pad, exit: Exit) -> Self {
+        Teleporter {
+            pads: [a, b],
+            exit,
+            cooldown_ticks: default_cooldown_ticks(),
+            arena_angle: 0.0,
+        }
+    }
+
+    /// The pad `index` turned with the arena.
+    pub fn pad_now(&self, index: usize) -> Pad {
+        let pad = &self.pads[index];
+        if self.arena_angle == 0.0 {
+            return pad.clone();
+        }
+        let (sin, cos) = self.arena_angle.sin_cos();
+        Pad {
+            center: Vec2::new(
+                pad.center.x * cos - pad.center.y * sin,
+                pad.center.x * sin + pad.center.y * cos,
+            ),
+            radius: pad.radius,
+            angle: pad.angle + self.arena_angle,
+        }
+    }
+
+    /// The index of the pad entered by the ball during its last move.
+    pub fn entered(&self, ball: &Ball) -> Option<usize> {
+        (0..self.pads.len()).position(|index| {
+            let pad = self.pad_now(index);
+            distance_to_segment(pad.center, ball.position_previous, ball.position) <= pad.radius
+        })
+    }
+
+    /// Move the ball that entered the pad `entry` to the other pad.
+    pub fn teleport(&self, ball: &mut Ball, entry: usize) {
+        let from = self.pad_now(entry);
+        let to = self.pad_now(1 - entry);
+        let rotation = match self.exit {
+            Exit::Keep => 0.0,
+            Exit::Rotate => to.angle - from.angle,
+        };
+        let (sin, cos) = rotation.sin_cos();
+        let rotate = |v: Vec2| Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos);
+        ball.position = to.center + rotate(ball.position - from.center);
+        // no interpolation of the rendering between the pads
+        ball.position_previous = ball.position;
+        ball.mvt_dir = rotate(ball.mvt_dir);
+        ball.teleport_cooldown = self.cooldown_ticks;
+    }
+}
+
+/// A pair of pads between the rings, until there are levels.
+pub fn default_teleporters() -> Vec<Teleporter> {
+    let pad = |angle: f32| Pad {
+        center: Vec2::new(angle.cos(), angle.sin()) * 210.0,
+        radius: 14.0,
+        angle,
+    };
+    vec![Teleporter::new(pad(0.5 * PI), pad(PI), Exit::Rotate)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn teleporter(exit: Exit) -> Teleporter {
+        Teleporter::new(
+            Pad {
+                center: Vec2::new(100.0, 0.0),
+                radius: 10.0,
+                angle: 0.0,
+            },
+            Pad {
+                center: Vec2::new(0.0, 100.0),
+                radius: 10.0,
+                angle: 0.5 * PI,
+            },
+            exit,
+        )
+    }
+
+    #[test]
+    fn test_enter_a_pad() {
+        let teleporter = teleporter(Exit::Keep);
+        // the ball passes over the pad during the step
+        let ball = Ball::moving(Vec2::new(80.0, 5.0), Vec2::new(120.0, 5.0));
+        assert_eq!(teleporter.entered(&ball), Some(0));
+        let ball = Ball::moving(Vec2::new(80.0, 15.0), Vec2::new(120.0, 15.0));
+        assert_eq!(teleporter.entered(&ball), None);
+        let ball = Ball::moving(Vec2::new(0.0, 80.0), Vec2::new(0.0, 95.0));
+        assert_eq!(teleporter.entered(&ball), Some(1));
+    }
+
+    #[test]
+    fn test_teleport_keep_or_rotate() {
+        let mut ball = Ball::moving(Vec2::new(90.0, 0.0), Vec2::new(98.0, 0.0));
+        teleporter(Exit::Keep).teleport(&mut ball, 0);
+        assert_eq!(ball.position, Vec2::new(-2.0, 100.0));
+        assert_eq!(ball.position_previous, ball.position);
+        assert_eq!(ball.mvt_dir, Vec2::new(1.0, 0.0));
+        assert_eq!(ball.teleport_cooldown, 30);
+
+        let mut ball = Ball::moving(Vec2::new(90.0, 0.0), Vec2::new(98.0, 0.0));
+        teleporter(Exit::Rotate).teleport(&mut ball, 0);
+        assert!((ball.position - Vec2::new(0.0, 98.0)).length() < 1e-3);
+        assert!((ball.mvt_dir - Vec2::new(0.0, 1.0)).length() < 1e-5);
+        // and back
+        teleporter(Exit::Rotate).teleport(&mut ball, 1);
+        assert!((ball.position - Vec2::new(98.0, 0.0)).length() < 1e-3);
+        assert!((ball.mvt_dir - Vec2::new(1.0, 0.0)).length() < 1e-5);
+    }
+}
@@ -0,0 +1,61 @@
+/// Convert the variable frame duration into a number of fixed duration steps of the simulation.
+///
+/// The remaining time (less than one step) is kept for the next frame and is exposed as
+/// `alpha` to interpolate the rendering between the two last steps.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    pub step_seconds: f32,
+    /// upper bound of steps per frame, to not spiral when a frame is very long (startup, tab in background, ...)
+    pub max_steps: u32,
+    accumulator: f32,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        FixedTimestep::new(120.0)
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(steps_per_second: f32) -> Self {
+        FixedTimestep {
+            step_seconds: 1.0 / steps_per_second,
+            max_steps: (0.25 * steps_per_second) as u32,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Accumulate `delta_seconds` and return the number of steps to run.
+    pub fn advance(&mut self, delta_seconds: f32) -> u32 {
+        // the time beyond `max_steps` is dropped, not caught up later
+        self.accumulator = (self.accumulator + delta_seconds.max(0.0))
+            .min(self.max_steps as f32 * self.step_seconds);
+        let mut steps = 0;
+        while self.accumulator >= self.step_seconds && steps < self.max_steps {
+            self.accumulator -= self.step_seconds;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Fraction (in [0, 1[) of the next step already elapsed.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step_seconds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_frame_is_dropped() {
+        let mut timestep = FixedTimestep::new(120.0);
+        assert_eq!(timestep.advance(0.02), 2);
+        assert!((timestep.alpha() - 0.4).abs() < 1e-3);
+        // a tab in background for an hour
+        assert!(timestep.advance(3600.0) <= timestep.max_steps);
+        assert!(timestep.alpha() < 1.0);
+        assert_eq!(timestep.advance(1.0 / 120.0), 1);
+    }
+}
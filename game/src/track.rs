@@ -0,0 +1,454 @@
+//! Closed tracks for the rings that are not circles: ellipses, rounded squares, splines.
+//!
+//! A track is sampled as a closed polyline, counterclockwise around the center of the arena. A
+//! paddle on a track covers a length of the polyline (arc-length parameterisation) and the balls
+//! collide with its segments, so with the real normal of the track.
+
+use crate::physics::{distance_to_segment, find_segment_collision, Collision};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Number of points of the sampled ellipses, of each corner of a rounded square and of each span
+/// of a spline.
+const SAMPLES_ELLIPSE: usize = 256;
+const SAMPLES_CORNER: usize = 16;
+const SAMPLES_SPAN: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TrackShape {
+    Ellipse {
+        radius_x: f32,
+        radius_y: f32,
+    },
+    /// a square (axis aligned) with rounded corners
+    RoundedSquare {
+        half_size: f32,
+        corner_radius: f32,
+    },
+    /// closed Catmull-Rom spline through the points, counterclockwise around the center
+    Spline {
+        points: Vec<Vec2>,
+    },
+}
+
+impl TrackShape {
+    /// The same shape, `factor` times bigger (from the center).
+    pub fn scaled(&self, factor: f32) -> TrackShape {
+        match self {
+            TrackShape::Ellipse { radius_x, radius_y } => TrackShape::Ellipse {
+                radius_x: radius_x * factor,
+                radius_y: radius_y * factor,
+            },
+            TrackShape::RoundedSquare {
+                half_size,
+                corner_radius,
+            } => TrackShape::RoundedSquare {
+                half_size: half_size * factor,
+                corner_radius: corner_radius * factor,
+            },
+            TrackShape::Spline { points } => TrackShape::Spline {
+                points: points.iter().map(|point| *point * factor).collect(),
+            },
+        }
+    }
+
+    /// Check the sizes, and that the track goes once around the center (every ray from the center
+    /// crosses it once).
+    pub fn validate(&self) -> Result<(), String> {
+        // false for NaN too
+        let positive = |value: f32| value > 0.0;
+        match self {
+            TrackShape::Ellipse { radius_x, radius_y } => {
+                if !positive(*radius_x) || !positive(*radius_y) {
+                    return Err("the radii of an ellipse should be > 0".to_string());
+                }
+            }
+            TrackShape::RoundedSquare {
+                half_size,
+                corner_radius,
+            } => {
+                if !positive(*half_size) {
+                    return Err("half_size of a rounded square should be > 0".to_string());
+                }
+                if !(*corner_radius >= 0.0 && corner_radius <= half_size) {
+                    return Err(
+                        "corner_radius of a rounded square should be between 0 and half_size"
+                            .to_string(),
+                    );
+                }
+            }
+            TrackShape::Spline { points } => {
+                if points.len() < 3 {
+                    return Err("a spline needs at least 3 points".to_string());
+                }
+            }
+        }
+        let points = self.sample();
+        let mut turn = 0.0;
+        for (index, a) in points.iter().enumerate() {
+            let b = points[(index + 1) % points.len()];
+            if a.perp_dot(b) <= 0.0 {
+                return Err(
+                    "the track should go counterclockwise around the center, without going back"
+                        .to_string(),
+                );
+            }
+            turn += a.perp_dot(b).atan2(a.dot(b));
+        }
+        if (turn - 2.0 * PI).abs() > 1e-2 {
+            return Err("the track should go once around the center".to_string());
+        }
+        Ok(())
+    }
+
+    /// The closed polyline of the shape (the last point is not repeated).
+    fn sample(&self) -> Vec<Vec2> {
+        let mut points: Vec<Vec2> = vec![];
+        match self {
+            TrackShape::Ellipse { radius_x, radius_y } => {
+                for i in 0..SAMPLES_ELLIPSE {
+                    let t = 2.0 * PI * i as f32 / SAMPLES_ELLIPSE as f32;
+                    points.push(Vec2::new(radius_x * t.cos(), radius_y * t.sin()));
+                }
+            }
+            TrackShape::RoundedSquare {
+                half_size,
+                corner_radius,
+            } => {
+                let inset = half_size - corner_radius;
+                // the sides are the segments between the corners
+                for (corner, sign) in [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)]
+                    .iter()
+                    .enumerate()
+                {
+                    let center = Vec2::new(sign.0 * inset, sign.1 * inset);
+                    for i in 0..=SAMPLES_CORNER {
+                        let t = 0.5 * PI * (corner as f32 + i as f32 / SAMPLES_CORNER as f32);
+                        points.push(center + Vec2::new(t.cos(), t.sin()) * *corner_radius);
+                    }
+                }
+            }
+            TrackShape::Spline { points: controls } => {
+                let n = controls.len();
+                for span in 0..n {
+                    let p0 = controls[(span + n - 1) % n];
+                    let p1 = controls[span];
+                    let p2 = controls[(span + 1) % n];
+                    let p3 = controls[(span + 2) % n];
+                    for i in 0..SAMPLES_SPAN {
+                        let t = i as f32 / SAMPLES_SPAN as f32;
+                        let t2 = t * t;
+                        let t3 = t2 * t;
+                        points.push(
+                            (p1 * 2.0
+                                + (p2 - p0) * t
+                                + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+                                + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+                                * 0.5,
+                        );
+                    }
+                }
+            }
+        }
+        // no segment without length (eg corners without radius)
+        points.dedup_by(|b, a| (*b - *a).length() < 1e-4);
+        if points.len() > 1 && (points[0] - points[points.len() - 1]).length() < 1e-4 {
+            points.pop();
+        }
+        points
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Track {
+    /// the shape at rest, the track can be turned and scaled with the arena (see `place`)
+    pub shape: TrackShape,
+    points: Vec<Vec2>,
+    /// arc length from the first point to each point, then the length of the whole track
+    lengths: Vec<f32>,
+    /// `points` and `lengths` at rest
+    rest: (Vec<Vec2>, Vec<f32>),
+}
+
+impl Track {
+    pub fn new(shape: TrackShape) -> Self {
+        let points = shape.sample();
+        let mut lengths = vec![0.0];
+        for (index, point) in points.iter().enumerate() {
+            let next = points[(index + 1) % points.len()];
+            lengths.push(lengths[index] + (next - *point).length());
+        }
+        Track {
+            shape,
+            rest: (points.clone(), lengths.clone()),
+            points,
+            lengths,
+        }
+    }
+
+    /// Turn the track (around the center) by `rotation` and scale it by `scale` from its rest.
+    pub fn place(&mut self, rotation: f32, scale: f32) {
+        let (sin, cos) = rotation.sin_cos();
+        let (points, lengths) = &self.rest;
+        for (point, rest) in self.points.iter_mut().zip(points.iter()) {
+            *point = Vec2::new(rest.x * cos - rest.y * sin, rest.x * sin + rest.y * cos) * scale;
+        }
+        for (length, rest) in self.lengths.iter_mut().zip(lengths.iter()) {
+            *length = rest * scale;
+        }
+    }
+
+    /// The points of the closed polyline.
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    pub fn length(&self) -> f32 {
+        self.lengths[self.points.len()]
+    }
+
+    /// The segment (index) at the arc length `s` (wrapped), and the arc length along it.
+    fn locate(&self, s: f32) -> (usize, f32) {
+        let s = s.rem_euclid(self.length());
+        let index = self
+            .lengths
+            .partition_point(|length| *length <= s)
+            .saturating_sub(1)
+            .min(self.points.len() - 1);
+        (index, s - self.lengths[index])
+    }
+
+    fn segment(&self, index: usize) -> (Vec2, Vec2) {
+        (
+            self.points[index],
+            self.points[(index + 1) % self.points.len()],
+        )
+    }
+
+    /// The point at the arc length `s` (from the first point, counterclockwise).
+    pub fn point_at(&self, s: f32) -> Vec2 {
+        let (index, along) = self.locate(s);
+        let (a, b) = self.segment(index);
+        let length = (b - a).length();
+        if length == 0.0 {
+            a
+        } else {
+            a + (b - a) * (along / length)
+        }
+    }
+
+    /// The normal of the track toward the outside at the arc length `s`.
+    pub fn normal_at(&self, s: f32) -> Vec2 {
+        let (a, b) = self.segment(self.locate(s).0);
+        let tangent = b - a;
+        Vec2::new(tangent.y, -tangent.x).normalize()
+    }
+
+    /// Where the straight trajectory from `position` along `direction` first reaches the track:
+    /// `(distance along the trajectory (in length of direction), point, arc length of the point)`.
+    pub fn find_crossing(&self, position: Vec2, direction: Vec2) -> Option<(f32, Vec2, f32)> {
+        let mut best: Option<(f32, Vec2, f32)> = None;
+        for index in 0..self.points.len() {
+            let (a, b) = self.segment(index);
+            let edge = b - a;
+            let denominator = direction.perp_dot(edge);
+            if denominator == 0.0 {
+                continue;
+            }
+            let w = a - position;
+            let distance = w.perp_dot(edge) / denominator;
+            let along = w.perp_dot(direction) / denominator;
+            if distance >= 0.0
+                && (0.0..=1.0).contains(&along)
+                && best.is_none_or(|(best, _, _)| distance < best)
+            {
+                let point = a + edge * along;
+                let s = self.lengths[index] + (point - a).length();
+                best = Some((distance, point, s));
+            }
+        }
+        best
+    }
+
+    /// The arc length of the point of the track in the direction `angle` from the center.
+    pub fn arc_length_at_angle(&self, angle: f32) -> f32 {
+        self.find_crossing(Vec2::zero(), Vec2::new(angle.cos(), angle.sin()))
+            .map_or(0.0, |(_, _, s)| s)
+    }
+
+    /// The distance between the center and the track, in the direction `angle`.
+    pub fn radius_at(&self, angle: f32) -> f32 {
+        self.find_crossing(Vec2::zero(), Vec2::new(angle.cos(), angle.sin()))
+            .map_or(0.0, |(distance, _, _)| distance)
+    }
+
+    /// The min and the max distance between the center and the track.
+    pub fn radius_range(&self) -> (f32, f32) {
+        let mut range = (f32::MAX, 0.0f32);
+        for index in 0..self.points.len() {
+            let (a, b) = self.segment(index);
+            range.0 = range.0.min(distance_to_segment(Vec2::zero(), a, b));
+            range.1 = range.1.max(a.length());
+        }
+        range
+    }
+
+    /// The polyline of the track from the arc length `from` to `to` (`from <= to`, they can be
+    /// out of `[0, length]`).
+    pub fn portion(&self, from: f32, to: f32) -> Vec<Vec2> {
+        let mut points = vec![self.point_at(from)];
+        let n = self.points.len();
+        let (first, along) = self.locate(from);
+        // arc length of the first point of the turn, in the same turn as `from`
+        let mut turn_start = from - along - self.lengths[first];
+        // the points after `from`, until `to` (at most one turn)
+        for k in (first + 1)..=(first + n) {
+            let index = k % n;
+            if index == 0 {
+                turn_start += self.length();
+            }
+            if turn_start + self.lengths[index] >= to {
+                break;
+            }
+            points.push(self.points[index]);
+        }
+        points.push(self.point_at(to));
+        points
+    }
+
+    /// Collision of a ball moving from `previous` to `current` with the band of `range` around the
+    /// portion of the track from `center - half_length` to `center + half_length` (arc lengths).
+    pub fn find_collision(
+        &self,
+        previous: Vec2,
+        current: Vec2,
+        center: f32,
+        half_length: f32,
+        range: f32,
+    ) -> Option<Collision> {
+        let points = self.portion(center - half_length, center + half_length);
+        points
+            .windows(2)
+            .filter_map(|segment| {
+                find_segment_collision(previous, current, segment[0], segment[1], range)
+            })
+            .min_by(|a, b| {
+                a.ratio
+                    .partial_cmp(&b.ratio)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ellipse() -> Track {
+        Track::new(TrackShape::Ellipse {
+            radius_x: 200.0,
+            radius_y: 100.0,
+        })
+    }
+
+    #[test]
+    fn test_arc_length_parameterisation() {
+        let track = ellipse();
+        // Ramanujan's approximation of the perimeter
+        let (a, b) = (200.0f32, 100.0f32);
+        let perimeter = PI * (3.0 * (a + b) - ((3.0 * a + b) * (a + 3.0 * b)).sqrt());
+        assert!((track.length() - perimeter).abs() / perimeter < 1e-3);
+        assert!((track.point_at(0.0) - Vec2::new(200.0, 0.0)).length() < 1e-3);
+        assert!((track.point_at(track.length()) - Vec2::new(200.0, 0.0)).length() < 1e-3);
+        assert!((track.point_at(track.length() / 2.0) - Vec2::new(-200.0, 0.0)).length() < 1.0);
+        assert!((track.radius_at(0.5 * PI) - 100.0).abs() < 1e-2);
+        assert!((track.radius_at(PI) - 200.0).abs() < 1e-2);
+        assert!((track.normal_at(1.0) - Vec2::new(1.0, 0.0)).length() < 5e-2);
+        let s = track.arc_length_at_angle(0.5 * PI);
+        assert!((track.point_at(s) - Vec2::new(0.0, 100.0)).length() < 1e-2);
+        let (min, max) = track.radius_range();
+        assert!((min - 100.0).abs() < 1e-1 && (max - 200.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_place_with_the_arena() {
+        let mut track = ellipse();
+        let length = track.length();
+        track.place(0.5 * PI, 2.0);
+        assert!((track.length() - 2.0 * length).abs() < 1e-2);
+        // the long axis is now vertical
+        assert!((track.radius_at(0.5 * PI) - 400.0).abs() < 1e-2);
+        track.place(0.0, 1.0);
+        assert_eq!(track.length(), length);
+    }
+
+    #[test]
+    fn test_portion_across_the_start() {
+        let track = ellipse();
+        let points = track.portion(-10.0, 10.0);
+        assert!((points[0] - track.point_at(-10.0)).length() < 1e-3);
+        assert!((points[points.len() - 1] - track.point_at(10.0)).length() < 1e-3);
+        let length: f32 = points.windows(2).map(|s| (s[1] - s[0]).length()).sum();
+        assert!((length - 20.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_collision_with_the_real_normal() {
+        let track = Track::new(TrackShape::RoundedSquare {
+            half_size: 100.0,
+            corner_radius: 20.0,
+        });
+        // on the flat top side: the normal is vertical, not radial
+        let s = track.arc_length_at_angle(100f32.atan2(40.0));
+        let collision = track
+            .find_collision(Vec2::new(40.0, 60.0), Vec2::new(40.0, 110.0), s, 30.0, 7.0)
+            .unwrap();
+        assert!((collision.point.y - 93.0).abs() < 1e-3);
+        assert!((collision.normal - Vec2::new(0.0, -1.0)).length() < 1e-3);
+        // outside of the portion
+        assert!(track
+            .find_collision(
+                Vec2::new(-60.0, 60.0),
+                Vec2::new(-110.0, 110.0),
+                s,
+                30.0,
+                7.0
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_validate_shapes() {
+        assert!(TrackShape::Ellipse {
+            radius_x: 200.0,
+            radius_y: 100.0
+        }
+        .validate()
+        .is_ok());
+        assert!(TrackShape::RoundedSquare {
+            half_size: 100.0,
+            corner_radius: 0.0
+        }
+        .validate()
+        .is_ok());
+        let square = |points: &[(f32, f32)]| TrackShape::Spline {
+            points: points.iter().map(|(x, y)| Vec2::new(*x, *y)).collect(),
+        };
+        assert!(
+            square(&[(100.0, 0.0), (0.0, 100.0), (-100.0, 0.0), (0.0, -100.0)])
+                .validate()
+                .is_ok()
+        );
+        // clockwise
+        assert!(
+            square(&[(100.0, 0.0), (0.0, -100.0), (-100.0, 0.0), (0.0, 100.0)])
+                .validate()
+                .is_err()
+        );
+        // not around the center
+        assert!(square(&[(100.0, 0.0), (200.0, 0.0), (200.0, 100.0)])
+            .validate()
+            .is_err());
+    }
+}